@@ -1,3 +1,4 @@
+mod panel;
 mod state;
 
 pub use state::AppState;
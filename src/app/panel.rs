@@ -0,0 +1,191 @@
+use crate::sim::BoidsParams;
+use kiss3d::conrod::{
+    color, widget, widget_ids, Colorable, Labelable, Positionable, Sizeable, UiCell, Widget,
+};
+
+const PANEL_WIDTH: f64 = 260.0;
+const ROW_HEIGHT: f64 = 24.0;
+const ROW_SPACING: f64 = 4.0;
+const ROWS: f64 = 17.0;
+
+widget_ids! {
+    pub struct PanelIds {
+        panel,
+        separation_range,
+        cohesion_range,
+        alignment_strength,
+        coherence_strength,
+        min_speed,
+        max_speed,
+        attraction,
+        attraction_min_range,
+        half_angle,
+        blind_spot,
+        obstacle_lookahead,
+        avoidance_strength,
+        limit_neighbors,
+        max_neighbors,
+        dt,
+        save_button
+    }
+}
+
+/// Last finite values of the parameters that can be turned off, restored
+/// when they are turned back on.
+pub struct PanelMemory {
+    attraction_min_range: f32,
+    max_neighbors: usize,
+}
+
+impl Default for PanelMemory {
+    fn default() -> PanelMemory {
+        PanelMemory {
+            attraction_min_range: 1.0,
+            max_neighbors: 5,
+        }
+    }
+}
+
+/// Side panel of widgets bound to the parameters of a running simulation.
+/// Returns whether the user asked to save the parameters to a scenario file.
+pub fn params_panel(
+    ids: &PanelIds,
+    memory: &mut PanelMemory,
+    params: &mut BoidsParams,
+    ui: &mut UiCell,
+) -> bool {
+    widget::Canvas::new()
+        .w_h(PANEL_WIDTH, ROWS * (ROW_HEIGHT + ROW_SPACING) + 16.0)
+        .top_left_with_margins(48.0, 8.0)
+        .pad(8.0)
+        .color(color::DARK_CHARCOAL.alpha(0.8))
+        .set(ids.panel, ui);
+
+    let row_width = PANEL_WIDTH - 16.0;
+
+    // Distances and speeds span several orders of magnitude depending on the
+    // scenario, so they are edited digit by digit.
+    macro_rules! dialer {
+        ($field:ident, $min:expr, $max:expr, $precision:expr) => {
+            if let Some(value) = widget::NumberDialer::new(params.$field, $min, $max, $precision)
+                .label(stringify!($field))
+                .label_font_size(11)
+                .w_h(row_width, ROW_HEIGHT)
+                .down(ROW_SPACING)
+                .set(ids.$field, ui)
+            {
+                params.$field = value;
+            }
+        };
+    }
+
+    macro_rules! slider {
+        ($field:ident) => {
+            if let Some(value) = widget::Slider::new(params.$field, 0.0, 1.0)
+                .label(&format!("{}: {:.2}", stringify!($field), params.$field))
+                .label_font_size(11)
+                .w_h(row_width, ROW_HEIGHT)
+                .down(ROW_SPACING)
+                .set(ids.$field, ui)
+            {
+                params.$field = value;
+            }
+        };
+    }
+
+    // Perception cone angles, in degrees.
+    macro_rules! angle {
+        ($field:ident) => {
+            if let Some(value) = widget::Slider::new(params.perception.$field, 0.0, 180.0)
+                .label(&format!(
+                    "{}: {:.0} deg",
+                    stringify!($field),
+                    params.perception.$field
+                ))
+                .label_font_size(11)
+                .w_h(row_width, ROW_HEIGHT)
+                .down(ROW_SPACING)
+                .set(ids.$field, ui)
+            {
+                params.perception.$field = value;
+            }
+        };
+    }
+
+    if let Some(value) = widget::NumberDialer::new(params.separation_range, 0.0, 10.0, 4)
+        .label("separation_range")
+        .label_font_size(11)
+        .w_h(row_width, ROW_HEIGHT)
+        .mid_top_of(ids.panel)
+        .set(ids.separation_range, ui)
+    {
+        params.separation_range = value;
+    }
+    dialer!(cohesion_range, 0.0, 10.0, 4);
+    slider!(alignment_strength);
+    slider!(coherence_strength);
+    dialer!(min_speed, 0.0, 100.0, 4);
+    dialer!(max_speed, 0.0, 100.0, 4);
+
+    // An infinite range turns attraction off, and dialers can't show it.
+    let attracting = params.attraction_min_range.is_finite();
+    for attract in widget::Toggle::new(attracting)
+        .label("attraction")
+        .label_font_size(11)
+        .w_h(row_width, ROW_HEIGHT)
+        .down(ROW_SPACING)
+        .set(ids.attraction, ui)
+    {
+        params.attraction_min_range = if attract {
+            memory.attraction_min_range
+        } else {
+            std::f32::INFINITY
+        };
+    }
+    if params.attraction_min_range.is_finite() {
+        dialer!(attraction_min_range, 0.0, 100.0, 3);
+        memory.attraction_min_range = params.attraction_min_range;
+    }
+    // A null step would never advance the simulation.
+    dialer!(dt, 1e-4, 1.0, 4);
+
+    angle!(half_angle);
+    angle!(blind_spot);
+    dialer!(obstacle_lookahead, 0.0, 10.0, 3);
+    dialer!(avoidance_strength, 0.0, 100.0, 3);
+
+    let limited = params.max_neighbors != std::usize::MAX;
+    for limit in widget::Toggle::new(limited)
+        .label("limit neighbors")
+        .label_font_size(11)
+        .w_h(row_width, ROW_HEIGHT)
+        .down(ROW_SPACING)
+        .set(ids.limit_neighbors, ui)
+    {
+        params.max_neighbors = if limit {
+            memory.max_neighbors
+        } else {
+            std::usize::MAX
+        };
+    }
+    if params.max_neighbors != std::usize::MAX {
+        if let Some(value) = widget::NumberDialer::new(params.max_neighbors as f32, 0.0, 999.0, 0)
+            .label("max_neighbors")
+            .label_font_size(11)
+            .w_h(row_width, ROW_HEIGHT)
+            .down(ROW_SPACING)
+            .set(ids.max_neighbors, ui)
+        {
+            params.max_neighbors = value.round() as usize;
+        }
+        memory.max_neighbors = params.max_neighbors;
+    }
+
+    widget::Button::new()
+        .label("Save scenario")
+        .label_font_size(11)
+        .w_h(row_width, ROW_HEIGHT)
+        .mid_bottom_of(ids.panel)
+        .set(ids.save_button, ui)
+        .was_clicked()
+}
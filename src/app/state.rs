@@ -1,5 +1,9 @@
+use super::panel::{self, PanelIds, PanelMemory};
+use crate::clock;
+use crate::registry::{self, RegistryEntry};
 use crate::sim::Simulation;
 use kiss3d::conrod::widget_ids;
+use kiss3d::scene::SceneNode;
 use kiss3d::window::{State, Window};
 use std::cell::RefCell;
 use std::thread::LocalKey;
@@ -8,7 +12,8 @@ widget_ids! {
     struct Ids {
         canvas,
         play_pause_button,
-        restart_button
+        restart_button,
+        scenario_list
     }
 }
 
@@ -57,30 +62,60 @@ image_ids!(
     restart_press_64: "images/restart_press_64.png",
 );
 
-pub struct AppState<Sim> {
+type Init = Box<dyn Fn(&mut SceneNode, u64) -> Box<dyn Simulation>>;
+
+/// Longest frame time the simulation catches up with, in seconds. Past this,
+/// the simulation slows down rather than taking ever more steps per frame.
+const MAX_FRAME_TIME: f64 = 0.25;
+
+pub struct AppState {
     ids: Ids,
+    panel_ids: PanelIds,
+    panel_memory: PanelMemory,
     image_ids: ImageIds,
     running: bool,
-    group: kiss3d::scene::SceneNode,
-    sim: Sim,
+    group: SceneNode,
+    sim: Box<dyn Simulation>,
+    init: Init,
+    seed: u64,
+    entries: Vec<RegistryEntry>,
+    selected: Option<usize>,
+    /// Time of the previous frame, while running.
+    last_frame: Option<f64>,
+    /// Simulated time lagging behind wall-clock time, in seconds.
+    accumulator: f64,
     should_stop: &'static LocalKey<RefCell<bool>>,
 }
 
-impl<Sim: Simulation> AppState<Sim> {
+impl AppState {
     pub fn new(
         mut window: &mut kiss3d::window::Window,
+        title: &str,
+        seed: u64,
+        init: Init,
         should_stop: &'static LocalKey<RefCell<bool>>,
-    ) -> AppState<Sim> {
+    ) -> AppState {
         let mut group = window.add_group();
-        let sim = Sim::init(&mut group);
+        let sim = init(&mut group, seed);
         let ids = Ids::new(window.conrod_ui_mut().widget_id_generator());
+        let panel_ids = PanelIds::new(window.conrod_ui_mut().widget_id_generator());
         let image_ids = ImageIds::new(&mut window);
+        let entries = registry::with_registry(|r| r.entries().to_vec());
+        let selected = entries.iter().position(|e| e.name() == title);
 
         AppState {
             ids,
+            panel_ids,
+            panel_memory: PanelMemory::default(),
             image_ids,
             sim,
+            init,
+            seed,
             group,
+            entries,
+            selected,
+            last_frame: None,
+            accumulator: 0.0,
             running: true,
             should_stop,
         }
@@ -126,22 +161,96 @@ impl<Sim: Simulation> AppState<Sim> {
                 .x_direction_from(self.ids.play_pause_button, Direction::Backwards, 8.0)
                 .set(self.ids.restart_button, ui);
 
-            restart_btn.was_clicked()
+            let names: Vec<_> = self.entries.iter().map(|e| e.name()).collect();
+            let selected = widget::DropDownList::new(&names[..], self.selected)
+                .w(200.0)
+                .h(32.0)
+                .top_left_with_margin(8.0)
+                .set(self.ids.scenario_list, ui);
+
+            if let Some(selected) = selected {
+                let entry = self.entries[selected].clone();
+                self.init = Box::new(move |scene: &mut SceneNode, seed: u64| {
+                    entry.instantiate(scene, seed)
+                });
+                self.selected = Some(selected);
+            }
+
+            let save = match self.sim.params_mut() {
+                Some(params) => {
+                    panel::params_panel(&self.panel_ids, &mut self.panel_memory, params, ui)
+                }
+                None => false,
+            };
+            if save {
+                self.save_scenario();
+            }
+
+            restart_btn.was_clicked() || selected.is_some()
         };
 
         if reset {
             self.group.unlink();
             self.group = window.add_group();
-            self.sim = Sim::init(&mut self.group);
+            // Restarting replays the same run.
+            self.sim = (self.init)(&mut self.group, self.seed);
+            self.accumulator = 0.0;
+        }
+    }
+
+    /// Steps the simulation at its own fixed rate, as many times as needed to
+    /// catch up with wall-clock time, then renders it.
+    fn advance(&mut self) {
+        let dt = f64::from(self.sim.timestep());
+
+        if self.running && dt > 0.0 {
+            let now = clock::now();
+            let frame_time = self.last_frame.map_or(0.0, |last| now - last);
+            self.last_frame = Some(now);
+            self.accumulator += frame_time.min(MAX_FRAME_TIME);
+
+            while self.accumulator >= dt {
+                self.sim.update();
+                self.accumulator -= dt;
+            }
+        } else {
+            self.last_frame = None;
         }
+
+        let alpha = if dt > 0.0 { self.accumulator / dt } else { 0.0 };
+        self.sim.render(alpha as f32);
+    }
+
+    /// Writes the current simulation, with its tuned parameters, to a scenario
+    /// file named after the running entry.
+    fn save_scenario(&self) {
+        let scenario = match self.sim.scenario() {
+            Some(scenario) => scenario.to_toml(),
+            None => return,
+        };
+        let name = self
+            .selected
+            .map_or("scenario", |selected| self.entries[selected].name());
+
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            let path = format!("{}.tuned.toml", name);
+            match std::fs::write(&path, scenario) {
+                Ok(()) => eprintln!("saved scenario to {}", path),
+                Err(err) => eprintln!("could not save scenario to {}: {}", path, err),
+            }
+        }
+
+        // There is no filesystem on the web: log the scenario instead.
+        #[cfg(target_arch = "wasm32")]
+        stdweb::console!(log, format!("{}.tuned.toml:\n{}", name, scenario));
     }
 }
 
-impl<Sim: Simulation + 'static> State for AppState<Sim> {
+impl State for AppState {
     fn step(&mut self, window: &mut Window) {
-        if self.running {
-            self.sim.update();
-        }
+        self.advance();
+        self.sim.draw(window);
 
         self.gui(window);
 
@@ -1,22 +1,243 @@
-use kiss3d::light::Light;
-use kiss3d::window::Window;
-use std::cell::RefCell;
+use boids::BoidsSimulation;
 use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::process;
 
-mod app;
-mod controls;
-mod sim;
+const USAGE: &str = "\
+Usage: boid [OPTIONS] <SCENARIO>
+       boid --list
 
-thread_local! {
-    static SHOULD_STOP: RefCell<bool> = RefCell::new(false);
+SCENARIO is the name of a registered scenario or demo, or the path to a .toml
+scenario file.
+
+Options:
+  -l, --list                    List the available scenarios and demos
+  -s, --seed <N>                Seed of the simulation RNG
+  -n, --boids <N>               Number of boids
+      --min-speed <X>
+      --max-speed <X>
+      --separation-range <X>
+      --cohesion-range <X>
+      --alignment-strength <X>
+      --coherence-strength <X>
+      --attraction-min-range <X>
+      --max-neighbors <N>
+      --neighbor-index <NAME>   rtree, grid or brute_force
+      --neighbor-skin <X>       Distance boids may move before the neighbor
+                                index is rebuilt
+      --dt <SECONDS>            Duration of a simulation step
+      --integrator <NAME>       explicit_euler, semi_implicit_euler,
+                                velocity_verlet or rk4
+      --predators <N>           Number of predators hunting the flock
+      --leaders <X>             Fraction of informed boids leading the flock
+      --noise <X>               Angular noise on headings, in [0, 1]
+  -p, --param <KEY=VALUE>       Override any scenario field, e.g. params.cohesion_range=0.2
+      --steps <N>               Run headless for N steps instead of opening a window
+  -o, --output <PATH>           Where to write the final boid states as CSV in
+                                headless mode (defaults to stdout)
+      --stats                   Print step timings, polarization, captures and
+                                leadership in headless mode
+  -h, --help                    Print this message";
+
+/// Options that map directly to a scenario field.
+const PARAM_OPTIONS: &[(&str, &str)] = &[
+    ("--boids", "boids"),
+    ("-n", "boids"),
+    ("--min-speed", "params.min_speed"),
+    ("--max-speed", "params.max_speed"),
+    ("--separation-range", "params.separation_range"),
+    ("--cohesion-range", "params.cohesion_range"),
+    ("--alignment-strength", "params.alignment_strength"),
+    ("--coherence-strength", "params.coherence_strength"),
+    ("--attraction-min-range", "params.attraction_min_range"),
+    ("--max-neighbors", "params.max_neighbors"),
+    ("--neighbor-index", "params.neighbor_index"),
+    ("--neighbor-skin", "params.neighbor_skin"),
+    ("--dt", "params.dt"),
+    ("--integrator", "params.integrator"),
+    ("--predators", "params.predators.count"),
+    ("--leaders", "params.leaders.fraction"),
+    ("--noise", "params.noise.angular"),
+];
+
+#[derive(Default)]
+struct Args {
+    list: bool,
+    help: bool,
+    scenario: Option<String>,
+    seed: Option<u64>,
+    overrides: Vec<(String, String)>,
+    steps: Option<usize>,
+    output: Option<String>,
+    stats: bool,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for {}", name))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => parsed.help = true,
+            "-l" | "--list" => parsed.list = true,
+            "-s" | "--seed" => {
+                let seed = value(&arg)?;
+                parsed.seed = Some(
+                    seed.parse()
+                        .map_err(|_| format!("invalid seed: {}", seed))?,
+                );
+            }
+            "--steps" => {
+                let steps = value(&arg)?;
+                parsed.steps = Some(
+                    steps
+                        .parse()
+                        .map_err(|_| format!("invalid step count: {}", steps))?,
+                );
+            }
+            "-o" | "--output" => parsed.output = Some(value(&arg)?),
+            "--stats" => parsed.stats = true,
+            "-p" | "--param" => {
+                let param = value(&arg)?;
+                let mut split = param.splitn(2, '=');
+                match (split.next(), split.next()) {
+                    (Some(key), Some(val)) => {
+                        parsed.overrides.push((key.to_string(), val.to_string()))
+                    }
+                    _ => return Err(format!("expected KEY=VALUE, got {}", param)),
+                }
+            }
+            _ => {
+                if let Some((_, key)) = PARAM_OPTIONS.iter().find(|(opt, _)| *opt == arg) {
+                    parsed.overrides.push((key.to_string(), value(&arg)?));
+                } else if arg.starts_with('-') {
+                    return Err(format!("unknown option: {}", arg));
+                } else if parsed.scenario.is_some() {
+                    return Err(format!("unexpected argument: {}", arg));
+                } else {
+                    parsed.scenario = Some(arg);
+                }
+            }
+        }
+    }
+
+    Ok(parsed)
+}
+
+fn list() {
+    boids::with_registry(|registry| {
+        for entry in registry.entries() {
+            println!("  {:<20}{}", entry.name(), entry.description());
+        }
+    });
+}
+
+fn write_csv<W: Write>(sim: &BoidsSimulation, mut out: W) -> io::Result<()> {
+    writeln!(out, "id,x,y,z,vx,vy,vz")?;
+    for boid in sim.boids() {
+        let (t, v) = (boid.translation, boid.velocity);
+        writeln!(
+            out,
+            "{},{},{},{},{},{},{}",
+            boid.id, t.x, t.y, t.z, v.x, v.y, v.z
+        )?;
+    }
+    out.flush()
+}
+
+fn run(args: Args) -> Result<(), String> {
+    let typ = args.scenario.ok_or("missing scenario")?;
+
+    let is_demo = boids::with_registry(|r| r.get(&typ).map_or(false, |e| e.scenario().is_none()));
+    if is_demo {
+        if !args.overrides.is_empty() || args.steps.is_some() {
+            return Err(format!("{} is a demo and takes no parameters", typ));
+        }
+        let seed = args.seed.unwrap_or_else(boids::random_seed);
+        eprintln!("seed: {}", seed);
+        return boids::start_simulation(&typ, Some(seed)).map_err(|err| err.to_string());
+    }
+
+    let mut scenario = boids::load_scenario(&typ).map_err(|err| err.to_string())?;
+    for (key, value) in &args.overrides {
+        scenario.set(key, value).map_err(|err| err.to_string())?;
+    }
+    if args.steps.is_none() && (args.output.is_some() || args.stats) {
+        return Err("--output and --stats require --steps".to_string());
+    }
+
+    let seed = args
+        .seed
+        .or(scenario.seed)
+        .unwrap_or_else(boids::random_seed);
+    eprintln!("seed: {}", seed);
+
+    match args.steps {
+        Some(steps) => {
+            let mut sim = scenario.build(seed);
+            for _ in 0..steps {
+                sim.update();
+            }
+            if args.stats {
+                eprintln!("{}", sim.stats());
+                eprintln!("polarization {:.3}", sim.polarization());
+                if !sim.predators().is_empty() {
+                    eprintln!(
+                        "{} boids caught by {} predators",
+                        sim.captures().len(),
+                        sim.predators().len()
+                    );
+                }
+                if let Some(leadership) = sim.leadership() {
+                    eprintln!("{}", leadership);
+                }
+            }
+
+            let stdout = io::stdout();
+            let written = match &args.output {
+                Some(path) => File::create(path).and_then(|f| write_csv(&sim, BufWriter::new(f))),
+                None => write_csv(&sim, stdout.lock()),
+            };
+            written.map_err(|err| format!("could not write output: {}", err))
+        }
+        None => {
+            boids::run_scenario(&typ, scenario, Some(seed));
+            Ok(())
+        }
+    }
 }
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
+    let args = match parse_args(env::args().skip(1)) {
+        Ok(args) => args,
+        Err(err) => {
+            eprintln!("error: {}\n\n{}", err, USAGE);
+            process::exit(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return;
+    }
 
-    if args.len() != 2 {
+    if args.list {
+        list();
         return;
     }
 
-    controls::start_simulation(&args[1]);
+    if args.scenario.is_none() {
+        eprintln!("{}", USAGE);
+        process::exit(2);
+    }
+
+    if let Err(err) = run(args) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
 }
@@ -0,0 +1,20 @@
+//! Wall clock that works both natively and on the web, where
+//! `std::time::Instant` is unavailable.
+
+/// Seconds elapsed since an arbitrary, fixed origin.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> f64 {
+    use std::time::Instant;
+
+    thread_local! {
+        static ORIGIN: Instant = Instant::now();
+    }
+
+    ORIGIN.with(|origin| origin.elapsed().as_secs_f64())
+}
+
+/// Seconds elapsed since an arbitrary, fixed origin.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> f64 {
+    stdweb::web::Date::now() / 1000.0
+}
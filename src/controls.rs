@@ -1,55 +1,76 @@
-use crate::{app, sim};
+use crate::{app, registry, sim};
+use kiss3d::scene::SceneNode;
 use std::cell::RefCell;
 
 thread_local! {
     static SHOULD_STOP: RefCell<bool> = RefCell::new(false);
 }
 
-macro_rules! match_sim {
-    ( $val:expr, $win:expr, $( $s:expr => $sim:path,)* ) => {
-        match $val {
-            $(
-                $s => {
-                    let state = app::AppState::<$sim>::new(
-                        &mut $win,
-                        &SHOULD_STOP,
-                    );
-                    $win.render_loop(state);
-                },
-            )*
-            _ => panic!("Unknown type: {}", $val),
-        }
-    };
-
-    ( $val:expr, $win:expr, $( $s:expr => $sim:path),* ) => {
-        match_sim!($val, $win, $($x: $path,)*)
-    };
+pub fn random_seed() -> u64 {
+    // Kept within 32 bits so that it can be passed back from JavaScript.
+    u64::from(rand::random::<u32>())
 }
 
-pub fn start_simulation(typ: &str) {
+/// Opens a window titled `title` and runs the simulation created by `init`
+/// from `seed` until the window is closed or `stop_simulation` is called.
+/// `init` is called again whenever the simulation is restarted.
+pub fn run_simulation<Sim, F>(title: &str, seed: u64, init: F)
+where
+    Sim: sim::Simulation + 'static,
+    F: Fn(&mut SceneNode, u64) -> Sim + 'static,
+{
     use kiss3d::light::Light;
     use kiss3d::window::Window;
 
-    let mut window = Window::new(typ);
+    let mut window = Window::new(title);
     window.set_light(Light::StickToCamera);
 
-    match_sim!(&typ[..], window,
-        "boid" => sim::sims::BoidSim,
-        "cube" => sim::sims::CubeSim,
-        "sphere_biased1" => sim::sims::SphereBiased1Sim,
-        "sphere_biased2" => sim::sims::SphereBiased2Sim,
-        "sphere" => sim::sims::SphereSim,
-        "distribution" => sim::sims::DistributionSim,
-        "no_constraints" => sim::sims::NoConstraintsSim,
-        "cohesion" => sim::sims::CohesionSim,
-        "separation" => sim::sims::SeparationSim,
-        "alignment" => sim::sims::AlignmentSim,
-        "attraction" => sim::sims::AttractionSim,
-        "coherence" => sim::sims::CoherenceSim,
-        "neighbors5_small" => sim::sims::Neighbors5SmallSim,
-        "neighbors5_big" => sim::sims::Neighbors5BigSim,
-        "leaders" => sim::sims::LeadersSim,
-    );
+    let init = Box::new(move |scene: &mut SceneNode, seed: u64| {
+        Box::new(init(scene, seed)) as Box<dyn sim::Simulation>
+    });
+    let state = app::AppState::new(&mut window, title, seed, init, &SHOULD_STOP);
+    window.render_loop(state);
+}
+
+/// Runs a scenario, seeded with `seed`, or with the scenario's own seed when
+/// `seed` is `None`. Callers that need to know the seed, e.g. to replay a
+/// run, should pick it themselves.
+pub fn run_scenario(title: &str, scenario: sim::Scenario, seed: Option<u64>) {
+    let seed = seed.or(scenario.seed).unwrap_or_else(random_seed);
+    run_simulation(title, seed, move |scene, seed| {
+        sim::ScenarioSim::new(&scenario, seed, scene)
+    });
+}
+
+/// Loads a scenario by path if `typ` names a `.toml` file, or from the
+/// registry otherwise.
+pub fn load_scenario(typ: &str) -> Result<sim::Scenario, sim::ScenarioError> {
+    if typ.ends_with(".toml") {
+        return sim::Scenario::from_file(typ);
+    }
+
+    registry::with_registry(|r| r.get(typ).and_then(|e| e.scenario().cloned()))
+        .ok_or_else(|| sim::ScenarioError::Unknown(typ.to_string()))
+}
+
+/// Runs a registered simulation by name, or a scenario file by path.
+pub fn start_simulation(typ: &str, seed: Option<u64>) -> Result<(), sim::ScenarioError> {
+    if typ.ends_with(".toml") {
+        run_scenario(typ, sim::Scenario::from_file(typ)?, seed);
+        return Ok(());
+    }
+
+    let entry = registry::with_registry(|r| r.get(typ).cloned())
+        .ok_or_else(|| sim::ScenarioError::Unknown(typ.to_string()))?;
+
+    match entry.scenario() {
+        Some(scenario) => run_scenario(typ, scenario.clone(), seed),
+        None => {
+            let seed = seed.unwrap_or_else(random_seed);
+            run_simulation(typ, seed, move |scene, seed| entry.instantiate(scene, seed));
+        }
+    }
+    Ok(())
 }
 
 pub fn stop_simulation() {
@@ -1,11 +1,39 @@
+//! A 3D boids flocking simulation.
+//!
+//! The flocking engine, `BoidsSimulation`, is plain data and can be stepped
+//! without a window. `BoidsView` renders it into a kiss3d scene, and
+//! `run_simulation` opens a window running any `Simulation`.
+
 mod app;
+mod clock;
 mod controls;
+mod registry;
 mod sim;
 
+pub use controls::{
+    load_scenario, random_seed, run_scenario, run_simulation, start_simulation, stop_simulation,
+};
+pub use registry::{
+    register_scenario, register_simulation, with_registry, Registry, RegistryEntry,
+};
+pub use sim::{
+    register_rule, sims, Agent, Alignment, Attraction, Attractor, Attractors, Avoidance, Boid,
+    BoidsParams, BoidsSimulation, BoidsSimulationBuilder, BoidsView, Boundary, BoundaryMode,
+    BruteForceIndex, Capture, Cohesion, FlashExpansion, Flee, Flock, Flow, FlowEffect, FlowField,
+    FlowForce, FlowGrid, Followers, GridIndex, Guidance, Integrator, Interaction, Keyframe,
+    LeaderParams, Leadership, Motion, Neighbor, NeighborIndex, NeighborIndexKind, NoiseParams,
+    Obstacle, Path, PathFollowing, Perception, Predator, PredatorParams, PredatorTarget,
+    RTreeIndex, ResponseCurve, RuleConfig, Scenario, ScenarioError, ScenarioSim, Separation,
+    Simulation, SpawnRegion, Species, SteeringRule, StepStats, Walls, BUNDLED_SCENARIOS,
+    DEFAULT_TIMESTEP,
+};
+
 #[cfg(target_arch = "wasm32")]
 #[stdweb::js_export]
-pub fn start(typ: String) {
-    controls::start_simulation(&typ);
+pub fn start(typ: String, seed: Option<u32>) {
+    if let Err(err) = controls::start_simulation(&typ, seed.map(u64::from)) {
+        stdweb::console!(error, err.to_string());
+    }
 }
 
 #[cfg(target_arch = "wasm32")]
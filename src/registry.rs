@@ -0,0 +1,168 @@
+use crate::sim::{self, Scenario, ScenarioSim, Simulation};
+use kiss3d::scene::SceneNode;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+type Factory = Rc<dyn Fn(&mut SceneNode, u64) -> Box<dyn Simulation>>;
+
+#[derive(Clone)]
+enum EntryKind {
+    Scenario(Scenario),
+    Custom(Factory),
+}
+
+/// A named simulation that can be started from the CLI, the GUI or the wasm
+/// `start` export.
+#[derive(Clone)]
+pub struct RegistryEntry {
+    name: String,
+    description: String,
+    kind: EntryKind,
+}
+
+impl RegistryEntry {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn description(&self) -> &str {
+        &self.description
+    }
+
+    /// Default parameters of the entry, if it is a scenario. Custom entries
+    /// are opaque and take no parameters.
+    pub fn scenario(&self) -> Option<&Scenario> {
+        match &self.kind {
+            EntryKind::Scenario(scenario) => Some(scenario),
+            EntryKind::Custom(_) => None,
+        }
+    }
+
+    /// Creates the simulation, adding whatever it renders to `scene`.
+    pub fn instantiate(&self, scene: &mut SceneNode, seed: u64) -> Box<dyn Simulation> {
+        match &self.kind {
+            EntryKind::Scenario(scenario) => Box::new(ScenarioSim::new(scenario, seed, scene)),
+            EntryKind::Custom(factory) => factory(scene, seed),
+        }
+    }
+}
+
+/// A list of named simulations.
+///
+/// The global registry, used by `start_simulation` and the GUI, starts out
+/// with the bundled scenarios and demos. Other crates can add their own
+/// entries at startup with `register_scenario` and `register_simulation`.
+#[derive(Clone, Default)]
+pub struct Registry {
+    entries: Vec<RegistryEntry>,
+}
+
+impl Registry {
+    pub fn new() -> Registry {
+        Default::default()
+    }
+
+    /// A registry holding the bundled scenarios and demos.
+    pub fn with_bundled() -> Registry {
+        let mut registry = Registry::new();
+
+        for (name, _) in sim::BUNDLED_SCENARIOS {
+            registry.register_scenario(name, Scenario::bundled(name).unwrap());
+        }
+
+        registry.register("boid", "The boid mesh, on its own.", |scene, seed| {
+            sim::sims::BoidSim::new(scene, seed)
+        });
+        registry.register(
+            "cube",
+            "Points uniformly distributed in a cube.",
+            |scene, seed| sim::sims::CubeSim::new(scene, seed),
+        );
+        registry.register(
+            "sphere_biased1",
+            "Points in a sphere, biased toward its center and poles.",
+            |scene, seed| sim::sims::SphereBiased1Sim::new(scene, seed),
+        );
+        registry.register(
+            "sphere_biased2",
+            "Points in a sphere, biased toward its center.",
+            |scene, seed| sim::sims::SphereBiased2Sim::new(scene, seed),
+        );
+        registry.register(
+            "sphere",
+            "Points uniformly distributed in a sphere.",
+            |scene, seed| sim::sims::SphereSim::new(scene, seed),
+        );
+        registry.register(
+            "distribution",
+            "Uniform and biased distributions of points in a disk.",
+            |scene, seed| sim::sims::DistributionSim::new(scene, seed),
+        );
+
+        registry
+    }
+
+    /// Registers a scenario. Its description is taken from the scenario. An
+    /// existing entry with the same name is replaced.
+    pub fn register_scenario(&mut self, name: &str, scenario: Scenario) {
+        self.insert(RegistryEntry {
+            name: name.to_string(),
+            description: scenario.description.clone(),
+            kind: EntryKind::Scenario(scenario),
+        });
+    }
+
+    /// Registers a custom simulation, created by `factory` from a scene and a
+    /// seed. An existing entry with the same name is replaced.
+    pub fn register<Sim, F>(&mut self, name: &str, description: &str, factory: F)
+    where
+        Sim: Simulation + 'static,
+        F: Fn(&mut SceneNode, u64) -> Sim + 'static,
+    {
+        self.insert(RegistryEntry {
+            name: name.to_string(),
+            description: description.to_string(),
+            kind: EntryKind::Custom(Rc::new(move |scene: &mut SceneNode, seed: u64| {
+                Box::new(factory(scene, seed)) as Box<dyn Simulation>
+            })),
+        });
+    }
+
+    fn insert(&mut self, entry: RegistryEntry) {
+        match self.entries.iter_mut().find(|e| e.name == entry.name) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Option<&RegistryEntry> {
+        self.entries.iter().find(|e| e.name == name)
+    }
+
+    pub fn entries(&self) -> &[RegistryEntry] {
+        &self.entries
+    }
+}
+
+thread_local! {
+    static REGISTRY: RefCell<Registry> = RefCell::new(Registry::with_bundled());
+}
+
+/// Gives access to the global registry.
+pub fn with_registry<R, F: FnOnce(&Registry) -> R>(f: F) -> R {
+    REGISTRY.with(|r| f(&r.borrow()))
+}
+
+/// Registers a scenario in the global registry.
+pub fn register_scenario(name: &str, scenario: Scenario) {
+    REGISTRY.with(|r| r.borrow_mut().register_scenario(name, scenario));
+}
+
+/// Registers a custom simulation in the global registry.
+pub fn register_simulation<Sim, F>(name: &str, description: &str, factory: F)
+where
+    Sim: Simulation + 'static,
+    F: Fn(&mut SceneNode, u64) -> Sim + 'static,
+{
+    REGISTRY.with(|r| r.borrow_mut().register(name, description, factory));
+}
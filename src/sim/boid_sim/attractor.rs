@@ -0,0 +1,192 @@
+use super::ResponseCurve;
+use nalgebra::{Rotation3, Unit, Vector3};
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+/// A point that pulls boids toward it, or pushes them away, as written in
+/// `[[params.attractors]]` tables of scenario files. See `Attractors`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Attractor {
+    /// Position at time 0.
+    pub position: Vector3<f32>,
+    /// Acceleration of boids at the attractor, in units per second squared.
+    /// Negative for a repulsor.
+    pub strength: f32,
+    /// Boids farther than this are not affected.
+    pub range: f32,
+    /// Boids closer than this are not affected.
+    pub min_range: f32,
+    /// Response curve of the attractor, instead of the one of the
+    /// `attractors` rule.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub falloff: Option<ResponseCurve>,
+    pub motion: Motion,
+}
+
+impl Default for Attractor {
+    fn default() -> Attractor {
+        Attractor {
+            position: Vector3::zeros(),
+            strength: 1.0,
+            range: std::f32::INFINITY,
+            min_range: 0.0,
+            falloff: None,
+            motion: Motion::Static,
+        }
+    }
+}
+
+/// How an `Attractor` moves. Positions are a function of the simulation time,
+/// so that changing the motion of an attractor may make it jump.
+///
+/// In scenario files, motions are written as inline tables tagged with their
+/// type, e.g. `motion = { type = "linear", velocity = [0.1, 0.0, 0.0] }`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Motion {
+    Static,
+    /// Moves at a constant `velocity`, in units per second.
+    Linear {
+        velocity: Vector3<f32>,
+    },
+    /// Turns around `center`, counterclockwise around `axis`, once every
+    /// `period` seconds.
+    Orbit {
+        center: Vector3<f32>,
+        #[serde(default = "default_axis")]
+        axis: Vector3<f32>,
+        period: f32,
+    },
+    /// Moves in straight lines between keyframes, sorted by time. The
+    /// attractor stays at the first position before the first keyframe, and
+    /// at the last one after the last keyframe unless `repeat` is set.
+    Keyframes {
+        #[serde(deserialize_with = "deserialize_keyframes")]
+        keyframes: Vec<Keyframe>,
+        #[serde(default)]
+        repeat: bool,
+    },
+}
+
+fn default_axis() -> Vector3<f32> {
+    Vector3::z()
+}
+
+fn deserialize_keyframes<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<Keyframe>, D::Error> {
+    let keyframes = Vec::<Keyframe>::deserialize(deserializer)?;
+    if keyframes.is_empty() {
+        return Err(de::Error::custom("a keyframe motion needs keyframes"));
+    }
+    if keyframes.iter().any(|keyframe| !keyframe.time.is_finite()) {
+        return Err(de::Error::custom("keyframe times must be finite"));
+    }
+    if keyframes.windows(2).any(|w| w[0].time >= w[1].time) {
+        return Err(de::Error::custom(
+            "keyframe times must be strictly increasing",
+        ));
+    }
+    Ok(keyframes)
+}
+
+impl Default for Motion {
+    fn default() -> Motion {
+        Motion::Static
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Keyframe {
+    /// Time of the keyframe, in seconds.
+    pub time: f32,
+    pub position: Vector3<f32>,
+}
+
+impl Attractor {
+    /// Position of the attractor `time` seconds after the simulation started.
+    pub fn position_at(&self, time: f32) -> Vector3<f32> {
+        match &self.motion {
+            Motion::Static => self.position,
+            Motion::Linear { velocity } => self.position + velocity * time,
+            Motion::Orbit {
+                center,
+                axis,
+                period,
+            } => {
+                let axis = match Unit::try_new(*axis, 0.0) {
+                    Some(axis) if *period != 0.0 => axis,
+                    _ => return self.position,
+                };
+                let angle = std::f32::consts::PI * 2.0 * time / period;
+                center + Rotation3::from_axis_angle(&axis, angle) * (self.position - center)
+            }
+            Motion::Keyframes { keyframes, repeat } => {
+                let (first, last) = match (keyframes.first(), keyframes.last()) {
+                    (Some(first), Some(last)) => (first, last),
+                    _ => return self.position,
+                };
+                let duration = last.time - first.time;
+                let time = if *repeat && duration > 0.0 {
+                    first.time + (time - first.time).rem_euclid(duration)
+                } else {
+                    time
+                };
+                match keyframes.iter().position(|keyframe| keyframe.time > time) {
+                    Some(0) => first.position,
+                    Some(i) => {
+                        let (a, b) = (&keyframes[i - 1], &keyframes[i]);
+                        let t = (time - a.time) / (b.time - a.time);
+                        a.position.lerp(&b.position, t)
+                    }
+                    None => last.position,
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        motion: Motion,
+    }
+
+    fn parse(times: &[&str]) -> Result<Motion, toml::de::Error> {
+        let keyframes: Vec<_> = times
+            .iter()
+            .enumerate()
+            .map(|(i, time)| format!("{{ time = {}, position = [{}.0, 0.0, 0.0] }}", time, i))
+            .collect();
+        let source = format!(
+            "motion = {{ type = \"keyframes\", keyframes = [{}] }}",
+            keyframes.join(", ")
+        );
+        toml::from_str::<Config>(&source).map(|config| config.motion)
+    }
+
+    #[test]
+    fn keyframes_interpolate() {
+        let attractor = Attractor {
+            motion: parse(&["1.0", "2.0", "4.0"]).unwrap(),
+            ..Attractor::default()
+        };
+        assert_eq!(attractor.position_at(0.0).x, 0.0);
+        assert_eq!(attractor.position_at(1.5).x, 0.5);
+        assert_eq!(attractor.position_at(3.0).x, 1.5);
+        assert_eq!(attractor.position_at(5.0).x, 2.0);
+    }
+
+    #[test]
+    fn keyframes_reject_bad_times() {
+        assert!(parse(&[]).is_err());
+        assert!(parse(&["2.0", "1.0"]).is_err());
+        assert!(parse(&["1.0", "1.0"]).is_err());
+        assert!(parse(&["0.0", "nan"]).is_err());
+        assert!(parse(&["1.0"]).is_ok());
+    }
+}
@@ -1,10 +1,8 @@
-use kiss3d::{resource::Mesh, scene::SceneNode};
-use nalgebra::{Point3, Rotation3, Vector3};
+use nalgebra::{Point3, Vector3};
+use rand::Rng;
 use rstar::{PointDistance, RTreeObject, AABB};
-use std::cell::RefCell;
-use std::rc::Rc;
 
-pub struct BoidDesc {
+pub(crate) struct BoidDesc {
     pub id: usize,
     pub position: Point3<f32>,
 }
@@ -24,11 +22,10 @@ impl RTreeObject for BoidDesc {
 }
 
 impl PointDistance for BoidDesc {
+    /// Squared distance, computed like `in_range` so that every neighbor
+    /// index agrees on which boids are in range.
     fn distance_2(&self, point: &[f32; 3]) -> f32 {
-        ((self.position.x - point[0]).powi(2)
-            + (self.position.y - point[1]).powi(2)
-            + (self.position.z - point[2]).powi(2))
-        .sqrt()
+        (self.position - Point3::new(point[0], point[1], point[2])).norm_squared()
     }
 
     fn contains_point(&self, _point: &[f32; 3]) -> bool {
@@ -36,82 +33,56 @@ impl PointDistance for BoidDesc {
     }
 }
 
+/// State of a single boid. Simulations store boids in a `Flock`, which hands
+/// out `Boid`s by value.
+#[derive(Clone, Debug, PartialEq)]
 pub struct Boid {
     pub id: usize,
+    /// Index of the boid's species in `BoidsParams::species`.
+    pub species: usize,
+    /// Whether the boid knows the preferred direction of the flock. See
+    /// `LeaderParams`.
+    pub informed: bool,
     pub translation: Vector3<f32>,
     pub velocity: Vector3<f32>,
-    pub neighbor_velocity: Vector3<f32>,
+    /// Acceleration at the start of the last step.
     pub acceleration: Vector3<f32>,
-    pub node: SceneNode,
-}
-
-thread_local! {
-    pub static BOID_MESH: Rc<RefCell<Mesh>> = Rc::new(RefCell::new(Mesh::new(
-        vec![
-            Point3::<f32>::new(
-                (std::f32::consts::PI * 1.0 / 2.0).cos(),
-                0.0,
-                (std::f32::consts::PI * 1.0 / 2.0).sin(),
-            ),
-            Point3::<f32>::new(
-                (std::f32::consts::PI * 7.0 / 6.0).cos(),
-                0.0,
-                (std::f32::consts::PI * 7.0 / 6.0).sin(),
-            ),
-            Point3::<f32>::new(
-                (std::f32::consts::PI * 11.0 / 6.0).cos(),
-                0.0,
-                (std::f32::consts::PI * 11.0 / 6.0).sin(),
-            ),
-            Point3::<f32>::new(0.0, 3.0, 0.0),
-        ],
-        vec![
-            Point3::<u16>::new(2, 1, 0),
-            Point3::<u16>::new(0, 1, 3),
-            Point3::<u16>::new(1, 2, 3),
-            Point3::<u16>::new(3, 2, 0),
-        ],
-        None,
-        None,
-        false,
-    )));
 }
 
 impl Boid {
-    pub fn generate_sphere(
+    /// Generates `n` boids uniformly distributed in a sphere centered on the
+    /// origin, heading in random directions.
+    pub fn generate_sphere<R: Rng + ?Sized>(
         n: usize,
         sphere_radius: f32,
         min_speed: f32,
         max_speed: f32,
-        scale: f32,
-        scene: &mut SceneNode,
+        rng: &mut R,
     ) -> Vec<Boid> {
         assert!(min_speed <= max_speed);
 
         (0..n)
             .map(|id| {
-                let r = sphere_radius * rand::random::<f32>().powf(1.0 / 3.0);
-                let theta = (2.0 * rand::random::<f32>() - 1.0).acos();
-                let phi = std::f32::consts::PI * 2.0 * rand::random::<f32>();
+                let r = sphere_radius * rng.gen::<f32>().powf(1.0 / 3.0);
+                let theta = (2.0 * rng.gen::<f32>() - 1.0).acos();
+                let phi = std::f32::consts::PI * 2.0 * rng.gen::<f32>();
                 let translation = Vector3::<f32>::new(
                     r * theta.sin() * phi.cos(),
                     r * theta.sin() * phi.sin(),
                     r * theta.cos(),
                 );
                 let velocity = Vector3::<f32>::new(
-                    rand::random::<f32>() - 5e-1,
-                    rand::random::<f32>() - 5e-1,
-                    rand::random::<f32>() - 5e-1,
+                    rng.gen::<f32>() - 5e-1,
+                    rng.gen::<f32>() - 5e-1,
+                    rng.gen::<f32>() - 5e-1,
                 )
                 .normalize()
-                    * (min_speed + rand::random::<f32>() * (max_speed - min_speed));
+                    * (min_speed + rng.gen::<f32>() * (max_speed - min_speed));
                 Boid::new(
                     id,
                     translation,
                     velocity,
                     Vector3::<f32>::new(0.0, 0.0, 0.0),
-                    0.1 * scale,
-                    scene,
                 )
             })
             .collect()
@@ -122,41 +93,14 @@ impl Boid {
         translation: Vector3<f32>,
         velocity: Vector3<f32>,
         acceleration: Vector3<f32>,
-        scale: f32,
-        scene: &mut SceneNode,
     ) -> Boid {
-        let mut node = BOID_MESH
-            .with(|m| scene.add_mesh(Rc::clone(m), Vector3::<f32>::new(1.0, 1.0, 1.0) * scale));
-
-        node.set_color(1.0, 0.0, 0.0);
-
-        let mut boid = Boid {
+        Boid {
             id,
+            species: 0,
+            informed: false,
             translation,
             velocity,
             acceleration,
-            node,
-            neighbor_velocity: Vector3::<f32>::new(0.0, 0.0, 0.0),
-        };
-        boid.update_node();
-        boid
-    }
-
-    pub fn reset(&mut self) {
-        self.acceleration = Vector3::<f32>::new(0.0, 0.0, 0.0);
-        self.neighbor_velocity = Vector3::<f32>::new(0.0, 0.0, 0.0);
-    }
-
-    pub fn update_node(&mut self) {
-        self.node.set_local_rotation(
-            Rotation3::<f32>::rotation_between(&Vector3::<f32>::y(), &self.velocity)
-                .unwrap()
-                .into(),
-        );
-        self.node.set_local_translation(self.translation.into());
-    }
-
-    pub fn desc(&self) -> BoidDesc {
-        BoidDesc::new(self.id, self.translation.into())
+        }
     }
 }
@@ -1,142 +1,831 @@
-use super::Boid;
+use super::{
+    assign_informed, assign_species, create_rule, detect_predators, par, Agent, Attractor,
+    Attributes, Boid, Boundary, BoundaryMode, Capture, Flock, FlockState, Flow, FlowEffect,
+    Integrator, LeaderParams, Leadership, Neighbor, NeighborCache, NeighborIndexKind, NoiseParams,
+    Obstacle, Path, Perception, Pipeline, Predator, PredatorParams, RuleConfig, Species, StepStats,
+};
+use crate::clock;
 use nalgebra::{Unit, Vector3};
-use rstar::{RTree, RTreeObject};
-use std::collections::HashMap;
+use rand::{rngs::StdRng, SeedableRng};
+use serde::{Deserialize, Serialize};
 
-const SEPARATION_FN: fn(f32) -> f32 = |t| t.powi(2) * 1e-2;
+/// Default duration of a simulation step, in seconds.
+pub const DEFAULT_TIMESTEP: f32 = 1.0 / 60.0;
 
-const COHESION_FN: fn(f32) -> f32 = |t| t.powi(2) * 1e-4;
-
-const ATTRACTION_FN: fn(f32) -> f32 = |d| d.powi(2) * 1e-3;
-
-const ALIGNMENT_FN: fn(f32) -> f32 = |t| t.powi(2) * 1e-2;
-
-pub struct BoidsSimulation {
-    pub boids: Vec<Boid>,
+/// Tunable parameters of a `BoidsSimulation`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct BoidsParams {
+    /// Point the flock is pulled toward. See `Attraction`, and `attractors`
+    /// for more points.
     pub attraction_center: Vector3<f32>,
+    /// Distance from `attraction_center` under which no attraction applies.
     pub attraction_min_range: f32,
+    /// Boids closer than this repel each other.
     pub separation_range: f32,
+    /// Boids between `separation_range` and this attract and align with each other.
     pub cohesion_range: f32,
+    /// Fraction of the way a boid turns toward its neighbors' heading every
+    /// 1/60 of a second, in `[0, 1]`.
     pub alignment_strength: f32,
+    /// How much the acceleration is bent back toward the current heading, in `[0, 1]`.
     pub coherence_strength: f32,
+    /// Speed limits, in units per second.
     pub max_speed: f32,
     pub min_speed: f32,
+    /// Number of nearest boids, not counting itself, that each boid picks as
+    /// neighbors among the boids within perception range. Two boids interact
+    /// if either picked the other.
+    #[serde(skip_serializing_if = "is_unbounded")]
     pub max_neighbors: usize,
+    /// Spatial index used to find neighbors.
+    pub neighbor_index: NeighborIndexKind,
+    /// Extra distance the neighbor index covers, so that it is only rebuilt
+    /// once a boid has moved by half of it. With 0, the index is rebuilt for
+    /// every step.
+    pub neighbor_skin: f32,
+    /// Duration of a step, in seconds.
+    pub dt: f32,
+    pub integrator: Integrator,
+    /// Distance ahead of them at which boids start avoiding obstacles.
+    pub obstacle_lookahead: f32,
+    /// Largest acceleration boids use to avoid obstacles.
+    pub avoidance_strength: f32,
+    /// Perception cone of the boids, for the rules that don't have their own.
+    pub perception: Perception,
+    /// World box. Open by default.
+    pub boundary: Boundary,
+    /// Predators, and how boids escape them. None by default.
+    pub predators: PredatorParams,
+    /// Informed boids, and how they lead the flock. None by default.
+    pub leaders: LeaderParams,
+    /// Random perturbations of velocities. None by default.
+    pub noise: NoiseParams,
+    /// Species of boids, by index. Empty by default, which is a single
+    /// species with the parameters above.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub species: Vec<Species>,
+    /// Static geometry to steer around. See `Avoidance`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub obstacles: Vec<Obstacle>,
+    /// Points that attract or repel boids, and may move. See `Attractors`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attractors: Vec<Attractor>,
+    /// Ambient flows, such as wind, that carry or push boids.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flows: Vec<Flow>,
+    /// Routes for boids to follow. See `PathFollowing`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub paths: Vec<Path>,
+    /// Steering rules, applied in order. Defaults to separation, cohesion,
+    /// alignment, attraction, avoidance, walls, flee, flash expansion,
+    /// guidance, path following, attractors and flow.
+    pub rules: Vec<RuleConfig>,
+}
+
+fn is_unbounded(max_neighbors: &usize) -> bool {
+    *max_neighbors == std::usize::MAX
+}
+
+impl Default for BoidsParams {
+    fn default() -> BoidsParams {
+        BoidsParams {
+            attraction_center: Vector3::<f32>::new(0.0, 0.0, 0.0),
+            attraction_min_range: std::f32::INFINITY,
+            separation_range: 0.0,
+            cohesion_range: 0.0,
+            alignment_strength: 0.0,
+            coherence_strength: 0.0,
+            max_speed: 1.8e-1,
+            min_speed: 1.8e-2,
+            max_neighbors: std::usize::MAX,
+            neighbor_index: NeighborIndexKind::default(),
+            neighbor_skin: 0.0,
+            dt: DEFAULT_TIMESTEP,
+            integrator: Integrator::default(),
+            obstacle_lookahead: 0.1,
+            avoidance_strength: 1.0,
+            perception: Perception::default(),
+            boundary: Boundary::default(),
+            predators: PredatorParams::default(),
+            leaders: LeaderParams::default(),
+            noise: NoiseParams::default(),
+            species: Vec::new(),
+            obstacles: Vec::new(),
+            paths: Vec::new(),
+            attractors: Vec::new(),
+            flows: Vec::new(),
+            rules: RuleConfig::defaults(),
+        }
+    }
+}
+
+/// Flocking simulation state. Stepping it with `update` does not require a
+/// window: use `BoidsView` to render it into a kiss3d scene.
+///
+/// All randomness goes through the simulation's own RNG, so two simulations
+/// built from the same seed replay identically.
+pub struct BoidsSimulation {
+    flock: Flock,
+    params: BoidsParams,
+    pipeline: Pipeline,
+    neighbors: NeighborCache,
+    predators: Vec<Predator>,
+    captures: Vec<Capture>,
+    steps: u64,
+    time: f64,
+    stats: StepStats,
+    rng: StdRng,
 }
 
 impl BoidsSimulation {
-    pub fn update(&mut self) {
-        // Build a tree for fast nearest neighbor search.
-        let mut tree = RTree::new();
-        for boid in &mut self.boids {
-            // Reset all boids at the start of the simulation step.
-            boid.reset();
-            tree.insert(boid.desc());
+    /// Rules of `params.rules` with unknown names are skipped. See
+    /// `unknown_rules`.
+    ///
+    /// Spawns `params.predators.count` predators with `rng`.
+    pub fn new(flock: Flock, params: BoidsParams, mut rng: StdRng) -> BoidsSimulation {
+        BoidsSimulation {
+            flock,
+            pipeline: Pipeline::new(&params.rules),
+            neighbors: NeighborCache::new(params.neighbor_index),
+            predators: Predator::spawn(&params.predators, &mut rng),
+            captures: Vec::new(),
+            steps: 0,
+            time: 0.0,
+            stats: StepStats::default(),
+            params,
+            rng,
         }
+    }
 
-        let mut visited = HashMap::<(usize, usize), ()>::new();
-        for bd1 in tree.iter() {
-            for (i, bd2) in tree
-                .nearest_neighbor_iter(&bd1.envelope().lower())
-                .enumerate()
-            {
-                if i > self.max_neighbors {
-                    break;
-                }
+    pub fn builder() -> BoidsSimulationBuilder {
+        BoidsSimulationBuilder::new()
+    }
 
-                if bd1.id == bd2.id {
-                    continue;
-                }
+    pub fn flock(&self) -> &Flock {
+        &self.flock
+    }
 
-                let pair = if bd1.id > bd2.id {
-                    (bd2.id, bd1.id)
-                } else {
-                    (bd1.id, bd2.id)
-                };
-                // Avoid re-visiting a pair of boids: everytime we visit a pair, we already update both boids together.
-                if visited.contains_key(&pair) {
-                    continue;
-                }
+    /// Boids can be added and removed between two steps.
+    pub fn flock_mut(&mut self) -> &mut Flock {
+        self.neighbors.invalidate();
+        &mut self.flock
+    }
+
+    pub fn boids(&self) -> impl Iterator<Item = Boid> + '_ {
+        self.flock.iter()
+    }
+
+    pub fn predators(&self) -> &[Predator] {
+        &self.predators
+    }
 
-                visited.insert(pair, ());
+    /// Predators can be added and removed between two steps.
+    pub fn predators_mut(&mut self) -> &mut Vec<Predator> {
+        &mut self.predators
+    }
 
-                let b1 = &self.boids[bd1.id];
-                let b2 = &self.boids[bd2.id];
+    /// Every boid caught by a predator so far, in order.
+    pub fn captures(&self) -> &[Capture] {
+        &self.captures
+    }
 
-                let travel = b2.translation - b1.translation;
-                let dist = travel.norm();
+    /// How well the flock follows its informed boids, or `None` if it has
+    /// none.
+    pub fn leadership(&self) -> Option<Leadership> {
+        Leadership::measure(&self.params.leaders, &self.params.boundary, &self.flock)
+    }
 
-                if dist > self.cohesion_range {
-                    break;
-                }
+    /// Order parameter of the flock: the norm of the mean heading of the
+    /// boids, 1 when they all head the same way and about 0 when their
+    /// headings are random.
+    pub fn polarization(&self) -> f32 {
+        let headings: Vector3<f32> = self
+            .flock
+            .velocities()
+            .iter()
+            .filter_map(|velocity| velocity.try_normalize(0.0))
+            .sum();
+        if self.flock.is_empty() {
+            0.0
+        } else {
+            headings.norm() / self.flock.len() as f32
+        }
+    }
+
+    /// Names of the rules of `params.rules` that no built-in or registered
+    /// rule matches. Steps skip them.
+    pub fn unknown_rules(&self) -> Vec<&str> {
+        self.params
+            .rules
+            .iter()
+            .filter(|config| create_rule(config).is_none())
+            .map(|config| config.rule.as_str())
+            .collect()
+    }
+
+    /// Number of steps since the simulation was created.
+    pub fn steps(&self) -> u64 {
+        self.steps
+    }
+
+    /// Simulated time since the simulation was created, in seconds.
+    pub fn time(&self) -> f64 {
+        self.time
+    }
+
+    pub fn params(&self) -> &BoidsParams {
+        &self.params
+    }
+
+    /// Parameters can be changed between two steps.
+    pub fn params_mut(&mut self) -> &mut BoidsParams {
+        &mut self.params
+    }
+
+    /// Counters accumulated since the simulation was created or the counters
+    /// were reset.
+    pub fn stats(&self) -> &StepStats {
+        &self.stats
+    }
 
-                if dist <= self.separation_range {
-                    // Separation
-                    let t = (self.separation_range - dist) / self.separation_range;
-                    let force = -travel * SEPARATION_FN(t);
-                    self.boids[bd1.id].acceleration += force;
-                    self.boids[bd2.id].acceleration += -force;
-                } else {
-                    // Cohesion
-                    let t = (self.cohesion_range - dist)
-                        / (self.cohesion_range - self.separation_range);
-                    let force = travel * COHESION_FN(t);
-
-                    self.boids[bd1.id].acceleration += force;
-                    self.boids[bd2.id].acceleration += -force;
-
-                    // Alignment
-                    let alignment_factor = ALIGNMENT_FN(t);
-                    let b1_vel = self.boids[bd1.id].velocity;
-                    let b2_vel = self.boids[bd2.id].velocity;
-                    self.boids[bd1.id].neighbor_velocity += alignment_factor * b2_vel;
-                    self.boids[bd2.id].neighbor_velocity += alignment_factor * b1_vel;
+    pub fn reset_stats(&mut self) {
+        self.stats = StepStats::default();
+    }
+
+    /// The simulation RNG. Use it for any random choice that should be
+    /// reproducible from the simulation seed.
+    pub fn rng_mut(&mut self) -> &mut StdRng {
+        &mut self.rng
+    }
+
+    /// Advances the simulation by one step of `params.dt` seconds, using
+    /// `params.integrator`.
+    ///
+    /// With the `parallel` feature, boids are updated on the rayon thread
+    /// pool. Every boid reads the state at the start of the step, so results
+    /// are the same with or without the feature.
+    ///
+    /// Noise, if any, perturbs velocities after the steering rules. Predators
+    /// move after the boids, then catch the boids within range.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `params.dt` is not positive and finite.
+    pub fn update(&mut self) {
+        check_timestep(self.params.dt);
+        let start = clock::now();
+        self.pipeline.sync(&self.params.rules);
+        self.pipeline.prepare(&self.params);
+        let (params, pipeline) = (&self.params, &self.pipeline);
+        let surroundings = Surroundings {
+            predators: &self.predators,
+            time: self.time as f32,
+        };
+        let predators = surroundings.predators;
+        let (neighbors, stats) = (&mut self.neighbors, &mut self.stats);
+        let FlockState {
+            translations,
+            velocities,
+            attributes,
+        } = self.flock.state_mut();
+
+        // Boids that detect a predator at the start of the step may speed up
+        // to the burst speed for the whole step.
+        let alarmed = if predators.is_empty() {
+            Vec::new()
+        } else {
+            par::map(translations.len(), |i| {
+                !detect_predators(
+                    &params.predators,
+                    &params.boundary,
+                    predators,
+                    &translations[i],
+                )
+                .is_empty()
+            })
+        };
+        let mut first_neighbors = None;
+        let clamp_speed = |i: usize, velocity: Vector3<f32>| {
+            let (min_speed, max_speed) = params.speed_limits(attributes.species[i]);
+            let max_speed = if alarmed.get(i) == Some(&true) {
+                params.predators.burst_speed.max(max_speed)
+            } else {
+                max_speed
+            };
+            // Speed control: ensure we don't accelerate past the max speed, or decelerate past the min speed.
+            // This ensures that boids have a minimum turn radius.
+            let speed = velocity.norm().max(min_speed).min(max_speed);
+            velocity.normalize() * speed
+        };
+
+        let accelerations = params.integrator.step(
+            translations,
+            velocities,
+            params.dt,
+            |translations, velocities| {
+                let neighbors = neighbors.find(
+                    params,
+                    translations,
+                    velocities,
+                    attributes,
+                    pipeline.range(params),
+                    stats,
+                );
+                let start = clock::now();
+                let accelerations = compute_accelerations(
+                    params,
+                    pipeline,
+                    surroundings,
+                    translations,
+                    velocities,
+                    attributes,
+                    &neighbors,
+                );
+                stats.rule_time += clock::now() - start;
+                if first_neighbors.is_none() {
+                    first_neighbors = Some(neighbors);
                 }
+                accelerations
+            },
+            clamp_speed,
+        );
+        // Noise is drawn one boid after the other, so that it replays from
+        // the seed with or without the `parallel` feature.
+        if params.noise.is_enabled() {
+            let rng = &mut self.rng;
+            for (i, velocity) in velocities.iter_mut().enumerate() {
+                let kicked = clamp_speed(i, *velocity + params.noise.kick(params.dt, rng));
+                *velocity = params.noise.turn(&kicked, rng);
             }
         }
-
-        for boid in &mut self.boids {
-            // Attraction
-            let delta = self.attraction_center - boid.translation;
-            let dist = delta.norm();
-            if dist >= self.attraction_min_range {
-                boid.acceleration +=
-                    delta.normalize() * ATTRACTION_FN(dist - self.attraction_min_range);
+        // Drifting flows carry boids from where they ended up.
+        if params
+            .flows
+            .iter()
+            .any(|flow| flow.effect == FlowEffect::Drift)
+        {
+            let drifts = par::map(translations.len(), |i| {
+                params
+                    .flows
+                    .iter()
+                    .filter(|flow| flow.effect == FlowEffect::Drift)
+                    .map(|flow| flow.sample(&translations[i], surroundings.time))
+                    .sum::<Vector3<f32>>()
+            });
+            for (translation, drift) in translations.iter_mut().zip(drifts) {
+                *translation += drift * params.dt;
             }
+        }
+        if let BoundaryMode::Periodic | BoundaryMode::Reflective = params.boundary.mode {
+            for (translation, velocity) in translations.iter_mut().zip(velocities.iter_mut()) {
+                params.boundary.constrain(translation, velocity);
+            }
+        }
+        self.flock.set_accelerations(accelerations);
+        self.steps += 1;
+        self.time += f64::from(params.dt);
+        if !self.predators.is_empty() {
+            self.update_predators(&first_neighbors.unwrap_or_default());
+        }
 
-            // Alignment
-            if boid.neighbor_velocity.norm() > 0.0 && boid.velocity.norm() > 0.0 {
-                let velocity_dir = Unit::new_normalize(boid.velocity);
-                let avg_neighbor_velocity_dir = Unit::new_normalize(boid.neighbor_velocity);
-                let wanted_velocity = velocity_dir
-                    .slerp(&avg_neighbor_velocity_dir, self.alignment_strength)
-                    .into_inner()
-                    * boid.velocity.norm();
-                boid.acceleration += wanted_velocity - boid.velocity;
+        self.stats.steps += 1;
+        self.stats.total_time += clock::now() - start;
+    }
+
+    /// `neighbors` are the neighbors of the boids at the start of the step.
+    fn update_predators(&mut self, neighbors: &[Vec<Neighbor>]) {
+        let (params, boundary) = (&self.params.predators, &self.params.boundary);
+        for predator in &mut self.predators {
+            predator.pick_target(params, boundary, &self.flock, neighbors);
+            predator.step(params, boundary, &self.flock, self.params.dt);
+        }
+
+        for (index, predator) in self.predators.iter_mut().enumerate() {
+            if let Some(i) = predator.catch(params, boundary, &self.flock) {
+                let id = self.flock.ids()[i];
+                let boid = self.flock.remove(id).unwrap();
+                self.neighbors.invalidate();
+                predator.captures += 1;
+                predator.rest = params.rest_time;
+                predator.target = None;
+                self.captures.push(Capture {
+                    step: self.steps,
+                    predator: index,
+                    boid,
+                });
             }
+        }
+    }
+}
+
+fn check_timestep(dt: f32) {
+    assert!(
+        dt > 0.0 && dt.is_finite(),
+        "invalid timestep: {} (must be positive and finite)",
+        dt
+    );
+}
+
+/// What boids react to besides each other, at the start of a step.
+#[derive(Clone, Copy)]
+struct Surroundings<'a> {
+    predators: &'a [Predator],
+    /// Time since the simulation started, in seconds.
+    time: f32,
+}
+
+/// Computes the acceleration of every boid, with boid `i` at
+/// `translations[i]` moving at `velocities[i]`.
+fn compute_accelerations(
+    params: &BoidsParams,
+    pipeline: &Pipeline,
+    surroundings: Surroundings,
+    translations: &[Vector3<f32>],
+    velocities: &[Vector3<f32>],
+    attributes: Attributes,
+    neighbors: &[Vec<Neighbor>],
+) -> Vec<Vector3<f32>> {
+    let predators = surroundings.predators;
+    par::map(translations.len(), |i| {
+        let detected = if predators.is_empty() {
+            Vec::new()
+        } else {
+            detect_predators(
+                &params.predators,
+                &params.boundary,
+                predators,
+                &translations[i],
+            )
+        };
+        let boid = Agent {
+            index: i,
+            species: attributes.species[i],
+            informed: attributes.informed[i],
+            translation: translations[i],
+            velocity: velocities[i],
+            time: surroundings.time,
+            neighbors: &neighbors[i],
+            predators: &detected,
+        };
+        let mut acceleration = pipeline.acceleration(params, &boid);
+
+        if acceleration.norm() > 0.0 {
+            // Coherence: ensure direction of acceleration is not too far from
+            // the direction of the current velocity. Increases boids' turn radius.
+            let acceleration_dir = Unit::new_normalize(acceleration);
+            let velocity_dir = Unit::new_normalize(boid.velocity);
+            let new_acceleration_dir = acceleration_dir
+                .slerp(&velocity_dir, params.coherence_strength)
+                .into_inner();
+            acceleration = new_acceleration_dir * acceleration.norm();
+        }
+
+        acceleration
+    })
+}
+
+/// Builds a `BoidsSimulation`.
+///
+/// ```no_run
+/// use boids::BoidsSimulation;
+///
+/// let mut sim = BoidsSimulation::builder()
+///     .seed(42)
+///     .sphere(100, 1e-1)
+///     .separation_range(3e-2)
+///     .cohesion_range(1.5e-1)
+///     .build();
+/// sim.update();
+/// ```
+#[derive(Default)]
+pub struct BoidsSimulationBuilder {
+    boids: Vec<Boid>,
+    sphere: Option<(Vector3<f32>, usize, f32)>,
+    params: BoidsParams,
+    seed: u64,
+}
+
+impl BoidsSimulationBuilder {
+    pub fn new() -> BoidsSimulationBuilder {
+        Default::default()
+    }
+
+    /// Seeds the simulation RNG. Defaults to 0.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn boids(mut self, boids: Vec<Boid>) -> Self {
+        self.boids = boids;
+        self.sphere = None;
+        self
+    }
+
+    /// Spawns `n` boids in a sphere of radius `radius` with the simulation RNG,
+    /// using the speed limits set on the builder. See `Boid::generate_sphere`.
+    pub fn sphere(self, n: usize, radius: f32) -> Self {
+        self.sphere_at(Vector3::<f32>::new(0.0, 0.0, 0.0), n, radius)
+    }
+
+    /// Same as `sphere`, with the sphere centered on `center`.
+    pub fn sphere_at(mut self, center: Vector3<f32>, n: usize, radius: f32) -> Self {
+        self.sphere = Some((center, n, radius));
+        self
+    }
+
+    pub fn params(mut self, params: BoidsParams) -> Self {
+        self.params = params;
+        self
+    }
+
+    pub fn attraction(mut self, center: Vector3<f32>, min_range: f32) -> Self {
+        self.params.attraction_center = center;
+        self.params.attraction_min_range = min_range;
+        self
+    }
+
+    pub fn separation_range(mut self, separation_range: f32) -> Self {
+        self.params.separation_range = separation_range;
+        self
+    }
+
+    pub fn cohesion_range(mut self, cohesion_range: f32) -> Self {
+        self.params.cohesion_range = cohesion_range;
+        self
+    }
+
+    pub fn alignment_strength(mut self, alignment_strength: f32) -> Self {
+        self.params.alignment_strength = alignment_strength;
+        self
+    }
+
+    pub fn coherence_strength(mut self, coherence_strength: f32) -> Self {
+        self.params.coherence_strength = coherence_strength;
+        self
+    }
+
+    pub fn speed(mut self, min_speed: f32, max_speed: f32) -> Self {
+        self.params.min_speed = min_speed;
+        self.params.max_speed = max_speed;
+        self
+    }
+
+    pub fn max_neighbors(mut self, max_neighbors: usize) -> Self {
+        self.params.max_neighbors = max_neighbors;
+        self
+    }
+
+    pub fn neighbor_index(mut self, neighbor_index: NeighborIndexKind) -> Self {
+        self.params.neighbor_index = neighbor_index;
+        self
+    }
+
+    pub fn neighbor_skin(mut self, neighbor_skin: f32) -> Self {
+        self.params.neighbor_skin = neighbor_skin;
+        self
+    }
+
+    /// Duration of a step, in seconds.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `dt` is not positive and finite.
+    pub fn dt(mut self, dt: f32) -> Self {
+        check_timestep(dt);
+        self.params.dt = dt;
+        self
+    }
+
+    pub fn integrator(mut self, integrator: Integrator) -> Self {
+        self.params.integrator = integrator;
+        self
+    }
+
+    pub fn perception(mut self, perception: Perception) -> Self {
+        self.params.perception = perception;
+        self
+    }
+
+    pub fn boundary(mut self, boundary: Boundary) -> Self {
+        self.params.boundary = boundary;
+        self
+    }
+
+    /// Adds a species. Boids spawned with `sphere` are split between species
+    /// by their shares.
+    pub fn species(mut self, species: Species) -> Self {
+        self.params.species.push(species);
+        self
+    }
+
+    /// Informs `fraction` of the boids spawned with `sphere`, which head in
+    /// `direction`. See `LeaderParams`.
+    pub fn leaders(mut self, fraction: f32, direction: Vector3<f32>) -> Self {
+        self.params.leaders.fraction = fraction;
+        self.params.leaders.direction = direction;
+        self
+    }
+
+    /// Adds a static obstacle.
+    pub fn obstacle(mut self, obstacle: Obstacle) -> Self {
+        self.params.obstacles.push(obstacle);
+        self
+    }
 
-            if boid.acceleration.norm() > 0.0 {
-                // Coherence: ensure direction of acceleration is not too far from
-                // the direction of the current velocity. Increases boids' turn radius.
-                let acceleration_dir = Unit::new_normalize(boid.acceleration);
-                let velocity_dir = Unit::new_normalize(boid.velocity);
-                let new_acceleration_dir = acceleration_dir
-                    .slerp(&velocity_dir, self.coherence_strength)
-                    .into_inner();
-                boid.acceleration = new_acceleration_dir * boid.acceleration.norm();
+    /// Adds a point that attracts or repels boids.
+    pub fn attractor(mut self, attractor: Attractor) -> Self {
+        self.params.attractors.push(attractor);
+        self
+    }
+
+    /// Adds an ambient flow.
+    pub fn flow(mut self, flow: Flow) -> Self {
+        self.params.flows.push(flow);
+        self
+    }
+
+    /// Adds a path for boids to follow.
+    pub fn path(mut self, path: Path) -> Self {
+        self.params.paths.push(path);
+        self
+    }
+
+    pub fn avoidance(mut self, lookahead: f32, strength: f32) -> Self {
+        self.params.obstacle_lookahead = lookahead;
+        self.params.avoidance_strength = strength;
+        self
+    }
+
+    /// Replaces the steering rules. See `RuleConfig`.
+    pub fn rules(mut self, rules: Vec<RuleConfig>) -> Self {
+        self.params.rules = rules;
+        self
+    }
+
+    pub fn build(self) -> BoidsSimulation {
+        let mut rng = StdRng::seed_from_u64(self.seed);
+        let boids = match self.sphere {
+            Some((center, n, radius)) => {
+                let mut boids = Boid::generate_sphere(
+                    n,
+                    radius,
+                    self.params.min_speed,
+                    self.params.max_speed,
+                    &mut rng,
+                );
+                for boid in &mut boids {
+                    boid.translation += center;
+                }
+                assign_species(&mut boids, &self.params.species);
+                assign_informed(&mut boids, self.params.leaders.fraction, &mut rng);
+                boids
             }
+            None => self.boids,
+        };
+        BoidsSimulation::new(boids.into_iter().collect(), self.params, rng)
+    }
+}
 
-            boid.velocity += boid.acceleration;
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-            // Speed control: ensure we don't accelerate past the max speed, or decelerate past the min speed.
-            // This ensures that boids have a minimum turn radius.
-            let speed = boid.velocity.norm().max(self.min_speed).min(self.max_speed);
-            boid.velocity = boid.velocity.normalize() * speed;
+    #[test]
+    fn unknown_rules_are_skipped() {
+        let mut sim = BoidsSimulation::builder()
+            .seed(1)
+            .sphere(20, 0.1)
+            .cohesion_range(0.15)
+            .rules(vec![RuleConfig::new("no_such_rule", 1.0)])
+            .build();
+        assert_eq!(sim.unknown_rules(), vec!["no_such_rule"]);
+        sim.update();
+
+        sim.params_mut()
+            .rules
+            .push(RuleConfig::new("another_one", 1.0));
+        sim.params_mut()
+            .rules
+            .push(RuleConfig::new("cohesion", 1.0));
+        assert_eq!(sim.unknown_rules(), vec!["no_such_rule", "another_one"]);
+        sim.update();
+        assert_eq!(sim.steps(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid timestep")]
+    fn builder_rejects_zero_timestep() {
+        BoidsSimulation::builder().dt(0.0);
+    }
 
-            // Apply velocity.
-            boid.translation += boid.velocity;
+    #[test]
+    #[should_panic(expected = "invalid timestep")]
+    fn update_rejects_zero_timestep() {
+        let mut sim = BoidsSimulation::builder().seed(1).sphere(10, 0.1).build();
+        sim.params_mut().dt = 0.0;
+        sim.update();
+    }
 
-            boid.update_node();
+    #[test]
+    fn burst_speed_never_slows_boids_down() {
+        let mut sim = BoidsSimulation::builder()
+            .seed(1)
+            .sphere(20, 0.05)
+            .speed(0.5, 0.5)
+            .build();
+        assert!(sim.params().predators.burst_speed < 0.5);
+        sim.predators_mut()
+            .push(Predator::new(Vector3::x() * 0.1, Vector3::x() * 0.1));
+        sim.update();
+        for velocity in sim.flock().velocities() {
+            assert!((velocity.norm() - 0.5).abs() < 1e-4, "{}", velocity.norm());
         }
     }
+
+    /// Angle between the headings of two boids that start `degrees` apart
+    /// and align for `duration` seconds, in steps of `dt`.
+    fn aligned_angle(degrees: f32, duration: f32, dt: f32) -> f32 {
+        let heading = |degrees: f32| {
+            let (sin, cos) = degrees.to_radians().sin_cos();
+            Vector3::new(cos, sin, 0.0) * 0.1
+        };
+        let boids = vec![
+            Boid::new(0, Vector3::zeros(), heading(0.0), Vector3::zeros()),
+            Boid::new(1, Vector3::z() * 0.05, heading(degrees), Vector3::zeros()),
+        ];
+        let mut sim = BoidsSimulation::builder()
+            .boids(boids)
+            .cohesion_range(0.15)
+            .alignment_strength(0.1)
+            .speed(0.05, 0.2)
+            .dt(dt)
+            .rules(vec![RuleConfig::new("alignment", 1.0)])
+            .build();
+        for _ in 0..(duration / dt).round() as usize {
+            sim.update();
+        }
+        let velocities = sim.flock().velocities();
+        velocities[0].angle(&velocities[1]).to_degrees()
+    }
+
+    #[test]
+    fn alignment_converges_in_wall_time() {
+        let coarse = aligned_angle(60.0, 0.1, 1.0 / 60.0);
+        let fine = aligned_angle(60.0, 0.1, 1.0 / 120.0);
+        assert!(coarse < 20.0, "{}", coarse);
+        assert!((coarse - fine).abs() < 1.5, "{} vs {}", coarse, fine);
+    }
+
+    fn noisy(seed: u64) -> BoidsSimulation {
+        let mut sim = BoidsSimulation::builder()
+            .seed(seed)
+            .sphere(100, 0.2)
+            .separation_range(0.03)
+            .cohesion_range(0.15)
+            .build();
+        sim.params_mut().noise = NoiseParams {
+            angular: 0.3,
+            force: 0.1,
+        };
+        sim
+    }
+
+    #[test]
+    fn noise_replays_from_seed() {
+        let (mut a, mut b, mut c) = (noisy(5), noisy(5), noisy(6));
+        for _ in 0..100 {
+            a.update();
+            b.update();
+            c.update();
+        }
+        assert_eq!(a.flock().translations(), b.flock().translations());
+        assert_eq!(a.flock().velocities(), b.flock().velocities());
+        assert_ne!(a.flock().translations(), c.flock().translations());
+    }
+
+    /// Parallel steps on any number of threads replay the sequential ones.
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn noise_replays_on_any_thread_count() {
+        let run = || {
+            let mut sim = noisy(5);
+            for _ in 0..100 {
+                sim.update();
+            }
+            sim.flock().translations().to_vec()
+        };
+        let single = rayon::ThreadPoolBuilder::new()
+            .num_threads(1)
+            .build()
+            .unwrap()
+            .install(run);
+        let multi = rayon::ThreadPoolBuilder::new()
+            .num_threads(4)
+            .build()
+            .unwrap()
+            .install(run);
+        assert_eq!(single, multi);
+    }
 }
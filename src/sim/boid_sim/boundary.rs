@@ -0,0 +1,133 @@
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// What happens to boids at the edges of the world box.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BoundaryMode {
+    /// No world box: boids go wherever their rules take them.
+    Open,
+    /// Boids leaving through a face come back through the opposite one, and
+    /// interact with the nearest image of each other across faces.
+    Periodic,
+    /// Boids bounce off the faces.
+    Reflective,
+    /// Boids are pushed back by the `walls` steering rule as they get closer
+    /// than `falloff` to a face.
+    Soft,
+}
+
+impl Default for BoundaryMode {
+    fn default() -> BoundaryMode {
+        BoundaryMode::Open
+    }
+}
+
+/// An axis-aligned world box and how boids behave at its faces.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Boundary {
+    pub mode: BoundaryMode,
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+    /// Soft walls: distance from a face at which boids start being pushed
+    /// back.
+    pub falloff: f32,
+    /// Soft walls: acceleration at a face.
+    pub strength: f32,
+}
+
+impl Default for Boundary {
+    fn default() -> Boundary {
+        Boundary {
+            mode: BoundaryMode::Open,
+            min: Vector3::new(-1.0, -1.0, -1.0),
+            max: Vector3::new(1.0, 1.0, 1.0),
+            falloff: 0.1,
+            strength: 1.0,
+        }
+    }
+}
+
+impl Boundary {
+    pub fn is_periodic(&self) -> bool {
+        self.mode == BoundaryMode::Periodic
+    }
+
+    pub fn size(&self) -> Vector3<f32> {
+        self.max - self.min
+    }
+
+    /// Offset from `from` to `to`. With periodic boundaries, this is the
+    /// offset to the nearest image of `to`, so that `offset(a, b)` is always
+    /// `-offset(b, a)`.
+    pub fn offset(&self, from: &Vector3<f32>, to: &Vector3<f32>) -> Vector3<f32> {
+        let offset = to - from;
+        if !self.is_periodic() {
+            return offset;
+        }
+        offset.zip_map(&self.size(), |d, size| {
+            if size > 0.0 {
+                d - size * (d / size).round()
+            } else {
+                d
+            }
+        })
+    }
+
+    /// Shifts to apply to a point so that the spheres of radius `range`
+    /// around the shifted points cover every image of the box the sphere
+    /// around the point reaches. Only the null shift unless periodic.
+    pub(crate) fn image_shifts(&self, point: &Vector3<f32>, range: f32) -> Vec<Vector3<f32>> {
+        let mut shifts = vec![Vector3::zeros()];
+        if !self.is_periodic() {
+            return shifts;
+        }
+        let size = self.size();
+        for axis in 0..3 {
+            let mut shift = Vector3::zeros();
+            let mut images = Vec::new();
+            if point[axis] - range < self.min[axis] {
+                shift[axis] = size[axis];
+                images.push(shift);
+            }
+            if point[axis] + range > self.max[axis] {
+                shift[axis] = -size[axis];
+                images.push(shift);
+            }
+            let combined: Vec<_> = shifts
+                .iter()
+                .flat_map(|shift| images.iter().map(move |image| shift + image))
+                .collect();
+            shifts.extend(combined);
+        }
+        shifts
+    }
+
+    /// Applies periodic or reflective boundaries to a boid that just moved.
+    pub fn constrain(&self, translation: &mut Vector3<f32>, velocity: &mut Vector3<f32>) {
+        let size = self.size();
+        for axis in 0..3 {
+            let (min, max) = (self.min[axis], self.max[axis]);
+            if size[axis] <= 0.0 {
+                continue;
+            }
+            match self.mode {
+                BoundaryMode::Periodic => {
+                    let x = translation[axis];
+                    translation[axis] = min + (x - min).rem_euclid(size[axis]);
+                }
+                BoundaryMode::Reflective => {
+                    if translation[axis] < min {
+                        translation[axis] = (2.0 * min - translation[axis]).min(max);
+                        velocity[axis] = velocity[axis].abs();
+                    } else if translation[axis] > max {
+                        translation[axis] = (2.0 * max - translation[axis]).max(min);
+                        velocity[axis] = -velocity[axis].abs();
+                    }
+                }
+                BoundaryMode::Open | BoundaryMode::Soft => return,
+            }
+        }
+    }
+}
@@ -0,0 +1,107 @@
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+/// Maps the input of a steering rule, usually a normalized distance, to the
+/// magnitude of its response.
+///
+/// In scenario files, curves are written as inline tables tagged with their
+/// type, e.g. `curve = { type = "polynomial", scale = 36.0, exponent = 2.0 }`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ResponseCurve {
+    /// `scale * x^exponent`.
+    Polynomial { scale: f32, exponent: f32 },
+    /// `scale * (e^(rate * x) - 1)`, which is null for `x = 0`.
+    Exponential { scale: f32, rate: f32 },
+    /// `scale / (x^2 + softening)`. `softening` keeps the response finite
+    /// for `x = 0`.
+    InverseSquare { scale: f32, softening: f32 },
+    /// Linear interpolation between `[x, y]` points, with strictly
+    /// increasing `x`. The response is constant before the first point and
+    /// after the last one. Scenarios with no points, or with points out of
+    /// order, are rejected.
+    PiecewiseLinear {
+        #[serde(deserialize_with = "deserialize_points")]
+        points: Vec<[f32; 2]>,
+    },
+}
+
+fn deserialize_points<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vec<[f32; 2]>, D::Error> {
+    let points = Vec::<[f32; 2]>::deserialize(deserializer)?;
+    if points.is_empty() {
+        return Err(de::Error::custom("a piecewise linear curve needs points"));
+    }
+    if points
+        .iter()
+        .any(|p| !p[0].is_finite() || !p[1].is_finite())
+    {
+        return Err(de::Error::custom("curve points must be finite"));
+    }
+    if points.windows(2).any(|w| w[0][0] >= w[1][0]) {
+        return Err(de::Error::custom(
+            "curve points must have strictly increasing x",
+        ));
+    }
+    Ok(points)
+}
+
+impl ResponseCurve {
+    pub fn polynomial(scale: f32, exponent: f32) -> ResponseCurve {
+        ResponseCurve::Polynomial { scale, exponent }
+    }
+
+    pub fn eval(&self, x: f32) -> f32 {
+        match self {
+            ResponseCurve::Polynomial { scale, exponent } => scale * x.powf(*exponent),
+            ResponseCurve::Exponential { scale, rate } => scale * ((rate * x).exp() - 1.0),
+            ResponseCurve::InverseSquare { scale, softening } => scale / (x * x + softening),
+            ResponseCurve::PiecewiseLinear { points } => {
+                let i = points.iter().position(|p| p[0] > x);
+                match i {
+                    None => points.last().map_or(0.0, |p| p[1]),
+                    Some(0) => points[0][1],
+                    Some(i) => {
+                        let ([x0, y0], [x1, y1]) = (points[i - 1], points[i]);
+                        y0 + (y1 - y0) * (x - x0) / (x1 - x0)
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        curve: ResponseCurve,
+    }
+
+    fn parse(points: &str) -> Result<ResponseCurve, toml::de::Error> {
+        let source = format!(
+            "curve = {{ type = \"piecewise_linear\", points = {} }}",
+            points
+        );
+        toml::from_str::<Config>(&source).map(|config| config.curve)
+    }
+
+    #[test]
+    fn piecewise_linear_interpolates() {
+        let curve = parse("[[0.0, 0.0], [1.0, 2.0], [2.0, 0.0]]").unwrap();
+        assert_eq!(curve.eval(-1.0), 0.0);
+        assert_eq!(curve.eval(0.5), 1.0);
+        assert_eq!(curve.eval(1.5), 1.0);
+        assert_eq!(curve.eval(3.0), 0.0);
+    }
+
+    #[test]
+    fn piecewise_linear_rejects_bad_points() {
+        assert!(parse("[]").is_err());
+        assert!(parse("[[1.0, 0.0], [0.0, 1.0]]").is_err());
+        assert!(parse("[[0.0, 0.0], [0.0, 1.0]]").is_err());
+        assert!(parse("[[0.0, nan], [1.0, 1.0]]").is_err());
+    }
+}
@@ -0,0 +1,169 @@
+use super::Boid;
+use nalgebra::Vector3;
+use std::collections::HashMap;
+use std::iter::FromIterator;
+
+/// The boids of a simulation, stored as one array per field so that the
+/// simulation loops run over contiguous memory.
+///
+/// Boid `i` is at `translations()[i]`, moves at `velocities()[i]` and so on.
+/// Indices are only valid until boids are removed; use ids to refer to a boid
+/// across steps.
+#[derive(Clone, Debug, Default)]
+pub struct Flock {
+    ids: Vec<usize>,
+    species: Vec<usize>,
+    informed: Vec<bool>,
+    translations: Vec<Vector3<f32>>,
+    velocities: Vec<Vector3<f32>>,
+    accelerations: Vec<Vector3<f32>>,
+    indices: HashMap<usize, usize>,
+}
+
+impl Flock {
+    pub fn new() -> Flock {
+        Default::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+
+    pub fn ids(&self) -> &[usize] {
+        &self.ids
+    }
+
+    pub fn species(&self) -> &[usize] {
+        &self.species
+    }
+
+    /// Whether each boid is informed. See `LeaderParams`.
+    pub fn informed(&self) -> &[bool] {
+        &self.informed
+    }
+
+    pub fn translations(&self) -> &[Vector3<f32>] {
+        &self.translations
+    }
+
+    pub fn velocities(&self) -> &[Vector3<f32>] {
+        &self.velocities
+    }
+
+    /// Accelerations at the start of the last step.
+    pub fn accelerations(&self) -> &[Vector3<f32>] {
+        &self.accelerations
+    }
+
+    /// Index of the boid with id `id`.
+    pub fn index_of(&self, id: usize) -> Option<usize> {
+        self.indices.get(&id).cloned()
+    }
+
+    /// The boid at index `index`.
+    pub fn boid(&self, index: usize) -> Boid {
+        Boid {
+            species: self.species[index],
+            informed: self.informed[index],
+            ..Boid::new(
+                self.ids[index],
+                self.translations[index],
+                self.velocities[index],
+                self.accelerations[index],
+            )
+        }
+    }
+
+    /// The boid with id `id`.
+    pub fn get(&self, id: usize) -> Option<Boid> {
+        self.index_of(id).map(|index| self.boid(index))
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = Boid> + '_ {
+        (0..self.len()).map(move |index| self.boid(index))
+    }
+
+    /// Adds a boid at the end of the flock.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the flock already has a boid with the same id.
+    pub fn push(&mut self, boid: Boid) {
+        let index = self.len();
+        assert!(
+            self.indices.insert(boid.id, index).is_none(),
+            "duplicate boid id: {}",
+            boid.id
+        );
+        self.ids.push(boid.id);
+        self.species.push(boid.species);
+        self.informed.push(boid.informed);
+        self.translations.push(boid.translation);
+        self.velocities.push(boid.velocity);
+        self.accelerations.push(boid.acceleration);
+    }
+
+    /// Removes the boid with id `id`. The last boid takes its index.
+    pub fn remove(&mut self, id: usize) -> Option<Boid> {
+        let index = self.indices.remove(&id)?;
+        let boid = self.boid(index);
+
+        self.ids.swap_remove(index);
+        self.species.swap_remove(index);
+        self.informed.swap_remove(index);
+        self.translations.swap_remove(index);
+        self.velocities.swap_remove(index);
+        self.accelerations.swap_remove(index);
+        if let Some(&moved) = self.ids.get(index) {
+            self.indices.insert(moved, index);
+        }
+
+        Some(boid)
+    }
+
+    /// Translations and velocities, for the simulation step.
+    pub(crate) fn state_mut(&mut self) -> FlockState<'_> {
+        FlockState {
+            translations: &mut self.translations,
+            velocities: &mut self.velocities,
+            attributes: Attributes {
+                species: &self.species,
+                informed: &self.informed,
+            },
+        }
+    }
+
+    pub(crate) fn set_accelerations(&mut self, accelerations: Vec<Vector3<f32>>) {
+        assert_eq!(accelerations.len(), self.len());
+        self.accelerations = accelerations;
+    }
+}
+
+impl FromIterator<Boid> for Flock {
+    fn from_iter<I: IntoIterator<Item = Boid>>(boids: I) -> Flock {
+        let mut flock = Flock::new();
+        for boid in boids {
+            flock.push(boid);
+        }
+        flock
+    }
+}
+
+/// The parts of a `Flock` a simulation step updates, and the attributes of
+/// the boids.
+pub(crate) struct FlockState<'a> {
+    pub translations: &'a mut [Vector3<f32>],
+    pub velocities: &'a mut [Vector3<f32>],
+    pub attributes: Attributes<'a>,
+}
+
+/// The parts of a `Flock` a simulation step reads but doesn't change.
+#[derive(Clone, Copy)]
+pub(crate) struct Attributes<'a> {
+    pub species: &'a [usize],
+    pub informed: &'a [bool],
+}
@@ -0,0 +1,319 @@
+use nalgebra::{Unit, Vector3};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::{fs, io};
+
+/// An ambient flow, such as wind or a current, as written in
+/// `[[params.flows]]` tables of scenario files.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flow {
+    pub effect: FlowEffect,
+    /// Factor applied to the field.
+    pub scale: f32,
+    /// Whether the window draws the field as arrows.
+    pub arrows: bool,
+    pub field: FlowField,
+}
+
+impl Default for Flow {
+    fn default() -> Flow {
+        Flow {
+            effect: FlowEffect::Drift,
+            scale: 1.0,
+            arrows: false,
+            field: FlowField::Uniform {
+                velocity: Vector3::zeros(),
+            },
+        }
+    }
+}
+
+impl Flow {
+    /// Scaled value of the field at `point`, `time` seconds after the
+    /// simulation started.
+    pub fn sample(&self, point: &Vector3<f32>, time: f32) -> Vector3<f32> {
+        self.field.sample(point, time) * self.scale
+    }
+}
+
+/// How a `Flow` acts on boids.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FlowEffect {
+    /// The field is a velocity that carries boids along, on top of their own
+    /// velocity.
+    Drift,
+    /// The field is an acceleration of boids. See the `flow` rule.
+    Force,
+}
+
+/// A vector field, in units per second for drifts and units per second
+/// squared for forces.
+///
+/// In scenario files, fields are written as inline tables tagged with their
+/// type, e.g. `field = { type = "uniform", velocity = [0.05, 0.0, 0.0] }`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FlowField {
+    /// The same everywhere.
+    Uniform { velocity: Vector3<f32> },
+    /// Turns counterclockwise around the line through `center` along `axis`,
+    /// as a solid body within `radius` and decaying with the distance beyond
+    /// it (a Rankine vortex). `speed` is the value at `radius`.
+    Vortex {
+        center: Vector3<f32>,
+        #[serde(default = "default_axis")]
+        axis: Vector3<f32>,
+        radius: f32,
+        speed: f32,
+    },
+    /// `velocity` on the side of the plane through `point` that `normal`
+    /// points to, and `-velocity` on the other side, with a smooth transition
+    /// over about `thickness` across the plane.
+    Shear {
+        point: Vector3<f32>,
+        normal: Vector3<f32>,
+        velocity: Vector3<f32>,
+        thickness: f32,
+    },
+    /// Divergence-free turbulence: the curl of gradient noise with features
+    /// about `1 / frequency` wide, and values of about `speed`. The pattern
+    /// scrolls by `drift` units per second.
+    CurlNoise {
+        frequency: f32,
+        speed: f32,
+        #[serde(default = "Vector3::zeros")]
+        drift: Vector3<f32>,
+        #[serde(default)]
+        seed: u32,
+    },
+    /// Values on a regular grid, read from a TOML file with the fields of a
+    /// `FlowGrid`, and interpolated between grid points. Points outside of
+    /// the grid take the value of the nearest point on its boundary.
+    Grid {
+        file: String,
+        /// Contents of `file`, once loaded with `load`.
+        #[serde(skip)]
+        grid: Option<Arc<FlowGrid>>,
+    },
+}
+
+fn default_axis() -> Vector3<f32> {
+    Vector3::z()
+}
+
+impl FlowField {
+    pub fn sample(&self, point: &Vector3<f32>, time: f32) -> Vector3<f32> {
+        match self {
+            FlowField::Uniform { velocity } => *velocity,
+            FlowField::Vortex {
+                center,
+                axis,
+                radius,
+                speed,
+            } => {
+                let axis = match Unit::try_new(*axis, 0.0) {
+                    Some(axis) => axis,
+                    None => return Vector3::zeros(),
+                };
+                let offset = point - center;
+                let radial = offset - axis.as_ref() * offset.dot(axis.as_ref());
+                let distance = radial.norm();
+                if distance == 0.0 || *radius <= 0.0 {
+                    return Vector3::zeros();
+                }
+                let value = if distance < *radius {
+                    speed * distance / radius
+                } else {
+                    speed * radius / distance
+                };
+                axis.cross(&radial) / distance * value
+            }
+            FlowField::Shear {
+                point: origin,
+                normal,
+                velocity,
+                thickness,
+            } => {
+                let across = match normal.try_normalize(0.0) {
+                    Some(normal) => (point - origin).dot(&normal),
+                    None => return Vector3::zeros(),
+                };
+                let t = if *thickness > 0.0 {
+                    (across / thickness).tanh()
+                } else {
+                    across.signum()
+                };
+                velocity * t
+            }
+            FlowField::CurlNoise {
+                frequency,
+                speed,
+                drift,
+                seed,
+            } => curl_noise(&((point - drift * time) * *frequency), *seed) * *speed,
+            FlowField::Grid { grid, .. } => grid
+                .as_ref()
+                .map_or_else(Vector3::zeros, |grid| grid.sample(point)),
+        }
+    }
+
+    /// Loads the file of a grid field, with relative paths taken from `dir`.
+    /// The path becomes relative to the working directory. Other fields have
+    /// nothing to load.
+    pub fn load(&mut self, dir: &std::path::Path) -> io::Result<()> {
+        if let FlowField::Grid { file, grid } = self {
+            let path = dir.join(&*file);
+            *grid = Some(Arc::new(FlowGrid::from_file(&path)?));
+            *file = path.to_string_lossy().into_owned();
+        }
+        Ok(())
+    }
+}
+
+/// Values of a field on a regular grid of `resolution[0]` by `resolution[1]`
+/// by `resolution[2]` points spanning the box from `min` to `max`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FlowGrid {
+    pub min: Vector3<f32>,
+    pub max: Vector3<f32>,
+    pub resolution: [usize; 3],
+    /// Values at the grid points, with x varying fastest, then y, then z.
+    pub values: Vec<Vector3<f32>>,
+}
+
+impl FlowGrid {
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> io::Result<FlowGrid> {
+        let grid: FlowGrid = toml::from_str(&fs::read_to_string(path)?)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+        let [nx, ny, nz] = grid.resolution;
+        if nx == 0 || ny == 0 || nz == 0 || grid.values.len() != nx * ny * nz {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "expected {} values for a resolution of {:?}, got {}",
+                    nx * ny * nz,
+                    grid.resolution,
+                    grid.values.len()
+                ),
+            ));
+        }
+        Ok(grid)
+    }
+
+    /// Trilinear interpolation of the values around `point`.
+    pub fn sample(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let mut cell = [0; 3];
+        let mut t = [0.0; 3];
+        for axis in 0..3 {
+            let n = self.resolution[axis];
+            let size = self.max[axis] - self.min[axis];
+            if n < 2 || size <= 0.0 {
+                continue;
+            }
+            let x =
+                ((point[axis] - self.min[axis]) / size * (n - 1) as f32).clamp(0.0, (n - 1) as f32);
+            cell[axis] = (x.floor() as usize).min(n - 2);
+            t[axis] = x - cell[axis] as f32;
+        }
+
+        let [nx, ny, _] = self.resolution;
+        let mut value = Vector3::zeros();
+        for corner in 0..8 {
+            let mut weight = 1.0;
+            let mut index = [0; 3];
+            for axis in 0..3 {
+                let high = (corner >> axis) & 1 == 1;
+                weight *= if high { t[axis] } else { 1.0 - t[axis] };
+                index[axis] = (cell[axis] + high as usize).min(self.resolution[axis] - 1);
+            }
+            if weight > 0.0 {
+                value += self.values[index[0] + nx * (index[1] + ny * index[2])] * weight;
+            }
+        }
+        value
+    }
+}
+
+/// Curl of a vector potential made of three gradient noises, computed with
+/// central differences.
+fn curl_noise(point: &Vector3<f32>, seed: u32) -> Vector3<f32> {
+    const EPSILON: f32 = 1e-2;
+    let potential = |point: Vector3<f32>| {
+        Vector3::new(
+            gradient_noise(&point, seed),
+            gradient_noise(&point, seed.wrapping_add(1)),
+            gradient_noise(&point, seed.wrapping_add(2)),
+        )
+    };
+    let derivative = |axis: usize| {
+        let mut offset = Vector3::zeros();
+        offset[axis] = EPSILON;
+        (potential(point + offset) - potential(point - offset)) / (2.0 * EPSILON)
+    };
+    let (dx, dy, dz) = (derivative(0), derivative(1), derivative(2));
+    Vector3::new(dy.z - dz.y, dz.x - dx.z, dx.y - dy.x)
+}
+
+/// Perlin's gradient noise, in about `[-1, 1]`.
+fn gradient_noise(point: &Vector3<f32>, seed: u32) -> f32 {
+    let cell = point.map(f32::floor);
+    let f = point - cell;
+    let fade = |t: f32| t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let (u, v, w) = (fade(f.x), fade(f.y), fade(f.z));
+
+    let corner = |dx: i32, dy: i32, dz: i32| {
+        let h = hash(
+            seed,
+            cell.x as i32 + dx,
+            cell.y as i32 + dy,
+            cell.z as i32 + dz,
+        );
+        let gradient = GRADIENTS[(h % GRADIENTS.len() as u32) as usize];
+        let offset = f - Vector3::new(dx as f32, dy as f32, dz as f32);
+        gradient[0] * offset.x + gradient[1] * offset.y + gradient[2] * offset.z
+    };
+    let lerp = |a: f32, b: f32, t: f32| a + (b - a) * t;
+    lerp(
+        lerp(
+            lerp(corner(0, 0, 0), corner(1, 0, 0), u),
+            lerp(corner(0, 1, 0), corner(1, 1, 0), u),
+            v,
+        ),
+        lerp(
+            lerp(corner(0, 0, 1), corner(1, 0, 1), u),
+            lerp(corner(0, 1, 1), corner(1, 1, 1), u),
+            v,
+        ),
+        w,
+    )
+}
+
+const GRADIENTS: [[f32; 3]; 12] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+];
+
+fn hash(seed: u32, x: i32, y: i32, z: i32) -> u32 {
+    let mut h = seed.wrapping_mul(0x9e37_79b9);
+    for &v in &[x, y, z] {
+        h ^= v as u32;
+        h = h.wrapping_mul(0x85eb_ca6b);
+        h ^= h >> 13;
+        h = h.wrapping_mul(0xc2b2_ae35);
+        h ^= h >> 16;
+    }
+    h
+}
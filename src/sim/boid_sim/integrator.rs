@@ -0,0 +1,215 @@
+use super::par;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Numerical scheme used to advance boids by one step.
+///
+/// Speed limits are enforced by projecting velocities back within bounds after
+/// every velocity update, including the intermediate stages of multi-stage
+/// schemes, so that forces are only ever evaluated on admissible states.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Integrator {
+    /// `x += v dt`, then `v += a dt`. First order, and unstable for stiff
+    /// forces such as tight separation.
+    ExplicitEuler,
+    /// `v += a dt`, then `x += v dt`. First order, but symplectic.
+    SemiImplicitEuler,
+    /// Second order. Evaluates accelerations twice per step.
+    VelocityVerlet,
+    /// Classic fourth-order Runge-Kutta. Evaluates accelerations four times
+    /// per step.
+    Rk4,
+}
+
+impl Default for Integrator {
+    fn default() -> Integrator {
+        Integrator::SemiImplicitEuler
+    }
+}
+
+impl Integrator {
+    /// Advances `translations` and `velocities` by `dt`. `acceleration`
+    /// evaluates the acceleration of every boid in a given state, and
+    /// `clamp(i, velocity)` projects the velocity of boid `i` back within its
+    /// speed limits.
+    ///
+    /// Returns the accelerations at the start of the step.
+    ///
+    /// Boids are updated in parallel with the `parallel` feature.
+    pub(crate) fn step<A, C>(
+        self,
+        translations: &mut [Vector3<f32>],
+        velocities: &mut [Vector3<f32>],
+        dt: f32,
+        mut acceleration: A,
+        clamp: C,
+    ) -> Vec<Vector3<f32>>
+    where
+        A: FnMut(&[Vector3<f32>], &[Vector3<f32>]) -> Vec<Vector3<f32>>,
+        C: Fn(usize, Vector3<f32>) -> Vector3<f32> + Send + Sync,
+    {
+        let a0 = acceleration(translations, velocities);
+
+        match self {
+            Integrator::ExplicitEuler => {
+                par::for_each_mut(translations, |i, x| *x += velocities[i] * dt);
+                par::for_each_mut(velocities, |i, v| *v = clamp(i, *v + a0[i] * dt));
+            }
+            Integrator::SemiImplicitEuler => {
+                par::for_each_mut(velocities, |i, v| *v = clamp(i, *v + a0[i] * dt));
+                par::for_each_mut(translations, |i, x| *x += velocities[i] * dt);
+            }
+            Integrator::VelocityVerlet => {
+                let predicted =
+                    par::map(velocities.len(), |i| clamp(i, velocities[i] + a0[i] * dt));
+                par::for_each_mut(translations, |i, x| {
+                    *x += velocities[i] * dt + a0[i] * (0.5 * dt * dt)
+                });
+                let a1 = acceleration(translations, &predicted);
+                par::for_each_mut(velocities, |i, v| {
+                    *v = clamp(i, *v + (a0[i] + a1[i]) * (0.5 * dt))
+                });
+            }
+            Integrator::Rk4 => {
+                // Evaluates the state `h` seconds along the derivative (`dx`, `dv`).
+                let offset = |h: f32, dx: &[Vector3<f32>], dv: &[Vector3<f32>]| {
+                    let x = par::map(translations.len(), |i| translations[i] + dx[i] * h);
+                    let v = par::map(velocities.len(), |i| clamp(i, velocities[i] + dv[i] * h));
+                    (x, v)
+                };
+
+                let (k1x, k1v) = (velocities.to_vec(), a0.clone());
+                let (x2, k2x) = offset(0.5 * dt, &k1x, &k1v);
+                let k2v = acceleration(&x2, &k2x);
+                let (x3, k3x) = offset(0.5 * dt, &k2x, &k2v);
+                let k3v = acceleration(&x3, &k3x);
+                let (x4, k4x) = offset(dt, &k3x, &k3v);
+                let k4v = acceleration(&x4, &k4x);
+
+                par::for_each_mut(translations, |i, x| {
+                    *x += (k1x[i] + k2x[i] * 2.0 + k3x[i] * 2.0 + k4x[i]) * (dt / 6.0)
+                });
+                par::for_each_mut(velocities, |i, v| {
+                    *v = clamp(
+                        i,
+                        *v + (k1v[i] + k2v[i] * 2.0 + k3v[i] * 2.0 + k4v[i]) * (dt / 6.0),
+                    )
+                });
+            }
+        }
+
+        a0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INTEGRATORS: [Integrator; 4] = [
+        Integrator::ExplicitEuler,
+        Integrator::SemiImplicitEuler,
+        Integrator::VelocityVerlet,
+        Integrator::Rk4,
+    ];
+
+    fn no_clamp(_: usize, v: Vector3<f32>) -> Vector3<f32> {
+        v
+    }
+
+    /// Runs a unit harmonic oscillator, `a = -x`, from `x = 1`, `v = 0`, and
+    /// returns its final position and velocity along x.
+    fn oscillate(integrator: Integrator, dt: f32, steps: usize) -> (f32, f32) {
+        let mut x = [Vector3::x()];
+        let mut v = [Vector3::zeros()];
+        for _ in 0..steps {
+            integrator.step(&mut x, &mut v, dt, |x, _| vec![-x[0]], no_clamp);
+        }
+        (x[0].x, v[0].x)
+    }
+
+    /// Error of the oscillator after `duration` seconds, against `cos t`.
+    fn error(integrator: Integrator, dt: f32, duration: f32) -> f32 {
+        let (x, v) = oscillate(integrator, dt, (duration / dt).round() as usize);
+        let (x_exact, v_exact) = (duration.cos(), -duration.sin());
+        ((x - x_exact).powi(2) + (v - v_exact).powi(2)).sqrt()
+    }
+
+    #[test]
+    fn order_of_accuracy() {
+        for &(integrator, order) in &[
+            (Integrator::ExplicitEuler, 1),
+            (Integrator::SemiImplicitEuler, 1),
+            (Integrator::VelocityVerlet, 2),
+            (Integrator::Rk4, 4),
+        ] {
+            let ratio = error(integrator, 0.1, 2.0) / error(integrator, 0.05, 2.0);
+            let expected = 2.0f32.powi(order);
+            assert!(
+                ratio > expected * 0.75 && ratio < expected * 1.25,
+                "{:?}: halving the step divides the error by {}, expected {}",
+                integrator,
+                ratio,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn energy_behavior() {
+        let energy = |integrator| {
+            let (x, v) = oscillate(integrator, 0.1, 1000);
+            0.5 * (x * x + v * v)
+        };
+        // The exact energy is 0.5 for ever.
+        assert!(energy(Integrator::ExplicitEuler) > 1.0);
+        let semi_implicit = energy(Integrator::SemiImplicitEuler);
+        assert!((semi_implicit - 0.5).abs() < 0.05, "{}", semi_implicit);
+        let verlet = energy(Integrator::VelocityVerlet);
+        assert!((verlet - 0.5).abs() < 0.01, "{}", verlet);
+        let rk4 = energy(Integrator::Rk4);
+        assert!(rk4 < 0.5 && rk4 > 0.49, "{}", rk4);
+    }
+
+    #[test]
+    fn clamp_applies_to_every_stage() {
+        let max_speed = 1.0;
+        let clamp = |_: usize, v: Vector3<f32>| {
+            if v.norm() > max_speed {
+                v.normalize() * max_speed
+            } else {
+                v
+            }
+        };
+        for &integrator in &INTEGRATORS {
+            let mut x = [Vector3::zeros(), Vector3::y()];
+            let mut v = [Vector3::x() * 0.9, Vector3::zeros()];
+            let mut evaluations = 0;
+            for _ in 0..10 {
+                integrator.step(
+                    &mut x,
+                    &mut v,
+                    0.1,
+                    |_, v| {
+                        evaluations += 1;
+                        for v in v {
+                            assert!(v.norm() <= max_speed * (1.0 + 1e-6), "{:?}", integrator);
+                        }
+                        vec![Vector3::x() * 50.0; 2]
+                    },
+                    clamp,
+                );
+                for v in &v {
+                    assert!(v.norm() <= max_speed * (1.0 + 1e-6), "{:?}", integrator);
+                }
+            }
+            let per_step = match integrator {
+                Integrator::ExplicitEuler | Integrator::SemiImplicitEuler => 1,
+                Integrator::VelocityVerlet => 2,
+                Integrator::Rk4 => 4,
+            };
+            assert_eq!(evaluations, 10 * per_step);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use super::{Boid, Boundary, Flock};
+use nalgebra::{Unit, Vector3};
+use rand::{seq::index, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Informed boids, which know a preferred direction or goal the rest of the
+/// flock doesn't, after Couzin et al., "Effective leadership and
+/// decision-making in animal groups on the move" (2005).
+///
+/// Informed boids follow the steering rules like any other boid, and the
+/// `guidance` rule turns them toward the preferred direction. Other boids only
+/// follow them through alignment and cohesion.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LeaderParams {
+    /// Fraction of the spawned boids that are informed, in `[0, 1]`.
+    pub fraction: f32,
+    /// Direction informed boids prefer, when there is no `goal`.
+    pub direction: Vector3<f32>,
+    /// Point informed boids head for, instead of `direction`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub goal: Option<Vector3<f32>>,
+    /// Informed boids closer than this to the goal stop heading for it.
+    pub goal_radius: f32,
+    /// How much informed boids favor the preferred direction over the
+    /// heading of their neighbors, the ω of Couzin et al.
+    pub weight: f32,
+    /// Weight of informed neighbors in alignment, relative to the others.
+    pub influence: f32,
+    /// Color of informed boids when rendered, as RGB in `[0, 1]`.
+    pub color: [f32; 3],
+}
+
+impl Default for LeaderParams {
+    fn default() -> LeaderParams {
+        LeaderParams {
+            fraction: 0.0,
+            direction: Vector3::new(1.0, 0.0, 0.0),
+            goal: None,
+            goal_radius: 0.1,
+            weight: 0.5,
+            influence: 1.0,
+            color: [1.0, 0.8, 0.0],
+        }
+    }
+}
+
+impl LeaderParams {
+    /// Direction informed boids at `translation` prefer, if any.
+    pub fn preferred_direction(
+        &self,
+        boundary: &Boundary,
+        translation: &Vector3<f32>,
+    ) -> Option<Unit<Vector3<f32>>> {
+        match self.goal {
+            Some(goal) => {
+                let offset = boundary.offset(translation, &goal);
+                if offset.norm() <= self.goal_radius {
+                    return None;
+                }
+                Unit::try_new(offset, 0.0)
+            }
+            None => Unit::try_new(self.direction, 0.0),
+        }
+    }
+}
+
+/// Informs `fraction` of `boids`, picked with `rng`.
+pub(crate) fn assign_informed<R: Rng + ?Sized>(boids: &mut [Boid], fraction: f32, rng: &mut R) {
+    let n = boids.len();
+    let count = ((fraction.clamp(0.0, 1.0) * n as f32).round() as usize).min(n);
+    if count == 0 {
+        return;
+    }
+    for i in index::sample(rng, n, count).into_iter() {
+        boids[i].informed = true;
+    }
+}
+
+/// How well a flock follows its informed boids.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Leadership {
+    /// Number of informed boids.
+    pub informed: usize,
+    /// Angle between the mean heading of the flock and the preferred
+    /// direction, in degrees. With a goal, the preferred direction is the one
+    /// from the center of the flock to the goal.
+    pub deviation: f32,
+    /// `1 - deviation / 180`: 1 when the flock heads the way its informed
+    /// boids want to, 0 when it heads the opposite way.
+    pub accuracy: f32,
+    /// Mean distance of the boids to the goal.
+    pub goal_distance: Option<f32>,
+    /// Fraction of the boids within `goal_radius` of the goal.
+    pub arrived: Option<f32>,
+}
+
+impl Leadership {
+    /// Measures `flock`, or returns `None` if it has no informed boids.
+    pub fn measure(
+        params: &LeaderParams,
+        boundary: &Boundary,
+        flock: &Flock,
+    ) -> Option<Leadership> {
+        let informed = flock
+            .informed()
+            .iter()
+            .filter(|&&informed| informed)
+            .count();
+        if informed == 0 {
+            return None;
+        }
+
+        let n = flock.len() as f32;
+        let heading: Vector3<f32> = flock
+            .velocities()
+            .iter()
+            .filter_map(|velocity| velocity.try_normalize(0.0))
+            .sum();
+        let (direction, goal_distance, arrived) = match params.goal {
+            Some(goal) => {
+                let offsets: Vec<_> = flock
+                    .translations()
+                    .iter()
+                    .map(|translation| boundary.offset(translation, &goal))
+                    .collect();
+                let center_offset = offsets.iter().sum::<Vector3<f32>>() / n;
+                let distances = offsets.iter().map(|offset| offset.norm());
+                let arrived = distances
+                    .clone()
+                    .filter(|&distance| distance <= params.goal_radius)
+                    .count();
+                (
+                    center_offset,
+                    Some(distances.sum::<f32>() / n),
+                    Some(arrived as f32 / n),
+                )
+            }
+            None => (params.direction, None, None),
+        };
+
+        let deviation = match (heading.try_normalize(0.0), direction.try_normalize(0.0)) {
+            (Some(heading), Some(direction)) => heading.angle(&direction).to_degrees(),
+            _ => 0.0,
+        };
+        Some(Leadership {
+            informed,
+            deviation,
+            accuracy: 1.0 - deviation / 180.0,
+            goal_distance,
+            arrived,
+        })
+    }
+}
+
+impl fmt::Display for Leadership {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} informed boids, heading {:.1} degrees off (accuracy {:.3})",
+            self.informed, self.deviation, self.accuracy
+        )?;
+        if let (Some(distance), Some(arrived)) = (self.goal_distance, self.arrived) {
+            write!(
+                f,
+                ", {:.3} from the goal on average, {:.1}% arrived",
+                distance,
+                arrived * 100.0
+            )?;
+        }
+        Ok(())
+    }
+}
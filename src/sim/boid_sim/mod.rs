@@ -1,5 +1,40 @@
+mod attractor;
 mod boid;
 mod boid_simulation;
+mod boundary;
+mod curve;
+mod flock;
+mod flow;
+mod integrator;
+mod leaders;
+mod neighbors;
+mod noise;
+mod obstacle;
+mod par;
+mod path;
+mod perception;
+mod predator;
+mod rules;
+mod species;
+mod stats;
+mod view;
 
+pub use attractor::*;
 pub use boid::*;
 pub use boid_simulation::*;
+pub use boundary::*;
+pub use curve::*;
+pub use flock::*;
+pub use flow::*;
+pub use integrator::*;
+pub use leaders::*;
+pub use neighbors::*;
+pub use noise::*;
+pub use obstacle::*;
+pub use path::*;
+pub use perception::*;
+pub use predator::*;
+pub use rules::*;
+pub use species::*;
+pub use stats::*;
+pub use view::*;
@@ -0,0 +1,616 @@
+use super::{par, Attributes, BoidDesc, BoidsParams, Neighbor, StepStats};
+use crate::clock;
+use nalgebra::Vector3;
+use rstar::RTree;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+/// Spatial index used to find the neighbors of boids.
+///
+/// Every backend finds the same neighbors: they only differ in speed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NeighborIndexKind {
+    /// An `rstar` R-tree.
+    #[serde(rename = "rtree")]
+    RTree,
+    /// A uniform grid of cells as large as the perception range. The fastest
+    /// when boids are spread out and the range is small.
+    Grid,
+    /// Compares every pair of boids. Only meant for small flocks and for
+    /// checking the other backends.
+    BruteForce,
+}
+
+impl Default for NeighborIndexKind {
+    fn default() -> NeighborIndexKind {
+        NeighborIndexKind::RTree
+    }
+}
+
+impl NeighborIndexKind {
+    pub fn create(self) -> Box<dyn NeighborIndex> {
+        match self {
+            NeighborIndexKind::RTree => Box::new(RTreeIndex::default()),
+            NeighborIndexKind::Grid => Box::new(GridIndex::default()),
+            NeighborIndexKind::BruteForce => Box::new(BruteForceIndex),
+        }
+    }
+}
+
+/// Finds the boids within a given range of a boid.
+///
+/// Queries may run on several threads at once.
+pub trait NeighborIndex: Send + Sync {
+    /// Indexes boids at `translations`, for queries up to `range`.
+    fn build(&mut self, translations: &[Vector3<f32>], range: f32);
+
+    /// Pushes to `out` every boid whose distance to `point` is at most
+    /// `range`, in any order. `translations` and `range` are the ones the
+    /// index was built with. `point` need not be inside the indexed region.
+    fn within_range(
+        &self,
+        translations: &[Vector3<f32>],
+        point: &Vector3<f32>,
+        range: f32,
+        out: &mut Vec<usize>,
+    );
+}
+
+/// Whether boid `j` is within `range` of `point`. All indices use this test,
+/// so they find exactly the same boids.
+pub(crate) fn in_range(
+    translations: &[Vector3<f32>],
+    point: &Vector3<f32>,
+    j: usize,
+    range: f32,
+) -> bool {
+    (translations[j] - point).norm_squared() <= range * range
+}
+
+#[derive(Default)]
+pub struct RTreeIndex {
+    tree: RTree<BoidDesc>,
+}
+
+impl NeighborIndex for RTreeIndex {
+    fn build(&mut self, translations: &[Vector3<f32>], _range: f32) {
+        self.tree = RTree::bulk_load(
+            translations
+                .iter()
+                .enumerate()
+                .map(|(id, translation)| BoidDesc::new(id, (*translation).into()))
+                .collect(),
+        );
+    }
+
+    fn within_range(
+        &self,
+        translations: &[Vector3<f32>],
+        point: &Vector3<f32>,
+        range: f32,
+        out: &mut Vec<usize>,
+    ) {
+        // The tree prunes its nodes with its own rounding: query a slightly
+        // larger radius and apply the exact test to what it finds.
+        let range_2 = range * range * (1.0 + 1e-5);
+        out.extend(
+            self.tree
+                .locate_within_distance([point.x, point.y, point.z], range_2)
+                .map(|bd| bd.id)
+                .filter(|&j| in_range(translations, point, j, range)),
+        );
+    }
+}
+
+#[derive(Default)]
+pub struct GridIndex {
+    cell_size: f32,
+    cells: HashMap<[i32; 3], Vec<usize>>,
+}
+
+impl GridIndex {
+    fn cell(&self, translation: &Vector3<f32>) -> [i32; 3] {
+        let cell = translation / self.cell_size;
+        [
+            cell.x.floor() as i32,
+            cell.y.floor() as i32,
+            cell.z.floor() as i32,
+        ]
+    }
+}
+
+impl NeighborIndex for GridIndex {
+    fn build(&mut self, translations: &[Vector3<f32>], range: f32) {
+        self.cell_size = range;
+        self.cells.clear();
+        for (id, translation) in translations.iter().enumerate() {
+            let cell = self.cell(translation);
+            self.cells.entry(cell).or_default().push(id);
+        }
+    }
+
+    fn within_range(
+        &self,
+        translations: &[Vector3<f32>],
+        point: &Vector3<f32>,
+        range: f32,
+        out: &mut Vec<usize>,
+    ) {
+        // Cells are as large as the range, so neighbors are in adjacent cells.
+        let [x, y, z] = self.cell(point);
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    if let Some(cell) = self.cells.get(&[x + dx, y + dy, z + dz]) {
+                        out.extend(
+                            cell.iter()
+                                .filter(|&&j| in_range(translations, point, j, range)),
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub struct BruteForceIndex;
+
+impl NeighborIndex for BruteForceIndex {
+    fn build(&mut self, _translations: &[Vector3<f32>], _range: f32) {}
+
+    fn within_range(
+        &self,
+        translations: &[Vector3<f32>],
+        point: &Vector3<f32>,
+        range: f32,
+        out: &mut Vec<usize>,
+    ) {
+        out.extend((0..translations.len()).filter(|&j| in_range(translations, point, j, range)));
+    }
+}
+
+/// A neighbor index kept from one step to the next.
+///
+/// The index is built for the perception range plus a skin distance, around
+/// the translations of the boids at that time. As long as no boid has moved
+/// by more than half the skin, every boid now within range was within range
+/// plus skin then, so the index still finds all candidates and only needs to
+/// be filtered with the current translations.
+pub(crate) struct NeighborCache {
+    kind: NeighborIndexKind,
+    index: Box<dyn NeighborIndex>,
+    /// Translations the index was built with, or `None` if it must be rebuilt.
+    reference: Option<Vec<Vector3<f32>>>,
+    /// Range plus skin the index was built for.
+    range: f32,
+    skin: f32,
+}
+
+impl NeighborCache {
+    pub fn new(kind: NeighborIndexKind) -> NeighborCache {
+        NeighborCache {
+            kind,
+            index: kind.create(),
+            reference: None,
+            range: 0.0,
+            skin: 0.0,
+        }
+    }
+
+    /// Leaves room for rounding errors between the build and the queries.
+    fn query_range(&self) -> f32 {
+        self.range * (1.0 + 1e-5)
+    }
+
+    /// Forces a rebuild, e.g. after boids were added or removed.
+    pub fn invalidate(&mut self) {
+        self.reference = None;
+    }
+
+    fn is_valid(
+        &self,
+        kind: NeighborIndexKind,
+        translations: &[Vector3<f32>],
+        range: f32,
+        skin: f32,
+    ) -> bool {
+        let reference = match &self.reference {
+            Some(reference) => reference,
+            None => return false,
+        };
+        let max_displacement = skin / 2.0;
+        kind == self.kind
+            && skin == self.skin
+            && range + skin == self.range
+            && reference.len() == translations.len()
+            && reference
+                .iter()
+                .zip(translations)
+                .all(|(a, b)| (b - a).norm() <= max_displacement)
+    }
+
+    /// Finds the neighbors of every boid.
+    ///
+    /// - Candidates of a boid are the other boids within `range` of it,
+    ///   inclusive. A boid is never its own candidate or neighbor.
+    /// - Each boid picks its `max_neighbors` nearest candidates. Ties in
+    ///   distance are broken by index, so a boid picks at most
+    ///   `max_neighbors` boids, not counting itself.
+    /// - Two boids are neighbors if either picked the other, so a boid can end
+    ///   up with more than `max_neighbors` neighbors.
+    ///
+    /// Neighbors are sorted by distance, then by index, so the result depends
+    /// neither on the index nor on the skin. With periodic boundaries,
+    /// distances and offsets are to the nearest image of each boid.
+    /// The index is `params.neighbor_index`, with a skin of
+    /// `params.neighbor_skin`.
+    pub fn find(
+        &mut self,
+        params: &BoidsParams,
+        translations: &[Vector3<f32>],
+        velocities: &[Vector3<f32>],
+        attributes: Attributes,
+        range: f32,
+        stats: &mut StepStats,
+    ) -> Vec<Vec<Neighbor>> {
+        let (kind, skin) = (params.neighbor_index, params.neighbor_skin);
+        let n = translations.len();
+        if range <= 0.0 {
+            return vec![Vec::new(); n];
+        }
+
+        if !self.is_valid(kind, translations, range, skin) {
+            let start = clock::now();
+            if kind != self.kind {
+                *self = NeighborCache::new(kind);
+            }
+            self.range = range + skin;
+            self.skin = skin;
+            self.index.build(translations, self.query_range());
+            self.reference = Some(translations.to_vec());
+            stats.index_rebuilds += 1;
+            stats.index_time += clock::now() - start;
+        }
+
+        let start = clock::now();
+        let neighbors = self.search(params, translations, velocities, attributes, range);
+        stats.neighbor_time += clock::now() - start;
+        neighbors
+    }
+
+    /// See `find`. The index must be valid.
+    fn search(
+        &self,
+        params: &BoidsParams,
+        translations: &[Vector3<f32>],
+        velocities: &[Vector3<f32>],
+        attributes: Attributes,
+        range: f32,
+    ) -> Vec<Vec<Neighbor>> {
+        let (index, reference) = (&*self.index, self.reference.as_ref().unwrap());
+        let reference_range = self.query_range();
+        let (max_neighbors, boundary) = (params.max_neighbors, &params.boundary);
+        let n = translations.len();
+
+        // Candidates are ranked by distance, then by index. The distance is
+        // the same whichever boid of a pair it is computed from.
+        let compare = |a: &(f32, usize), b: &(f32, usize)| {
+            a.0.partial_cmp(&b.0)
+                .unwrap_or(Ordering::Equal)
+                .then(a.1.cmp(&b.1))
+        };
+
+        let offset = |i: usize, j: usize| boundary.offset(&translations[i], &translations[j]);
+
+        let candidates = par::map(n, |i| {
+            let mut found = Vec::new();
+            for shift in boundary.image_shifts(&reference[i], reference_range) {
+                index.within_range(
+                    reference,
+                    &(reference[i] + shift),
+                    reference_range,
+                    &mut found,
+                );
+            }
+            if boundary.is_periodic() {
+                // A small box can hold several images of a boid within range.
+                found.sort_unstable();
+                found.dedup();
+            }
+            let mut candidates: Vec<_> = found
+                .into_iter()
+                .filter(|&j| j != i)
+                .filter_map(|j| {
+                    let distance_2 = offset(i, j).norm_squared();
+                    if distance_2 <= range * range {
+                        Some((distance_2.sqrt(), j))
+                    } else {
+                        None
+                    }
+                })
+                .collect();
+            candidates.sort_by(compare);
+            candidates
+        });
+
+        // Candidates are symmetric: `i` picked the candidate `key` if it
+        // ranks among the first `max_neighbors` candidates of `i`. This lets
+        // every boid gather its own neighbors without writing into the
+        // others'.
+        let picked = |i: usize, key: (f32, usize)| {
+            max_neighbors > 0
+                && match candidates[i].get(max_neighbors - 1) {
+                    Some(last) => compare(&key, last) != Ordering::Greater,
+                    None => true,
+                }
+        };
+
+        par::map(n, |i| {
+            candidates[i]
+                .iter()
+                .filter(|&&(distance, j)| picked(i, (distance, j)) || picked(j, (distance, i)))
+                .map(|&(distance, j)| Neighbor {
+                    index: j,
+                    offset: offset(i, j),
+                    species: attributes.species[j],
+                    informed: attributes.informed[j],
+                    distance,
+                    velocity: velocities[j],
+                })
+                .collect()
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::boid_sim::BoundaryMode;
+    use rand::{rngs::StdRng, Rng, SeedableRng};
+
+    const KINDS: [NeighborIndexKind; 3] = [
+        NeighborIndexKind::RTree,
+        NeighborIndexKind::Grid,
+        NeighborIndexKind::BruteForce,
+    ];
+
+    type Found = Vec<Vec<(usize, f32, Vector3<f32>)>>;
+
+    fn params(kind: NeighborIndexKind, periodic: bool, skin: f32) -> BoidsParams {
+        let mut params = BoidsParams {
+            neighbor_index: kind,
+            neighbor_skin: skin,
+            ..Default::default()
+        };
+        if periodic {
+            params.boundary.mode = BoundaryMode::Periodic;
+            params.boundary.min = Vector3::repeat(-0.5);
+            params.boundary.max = Vector3::repeat(0.5);
+        }
+        params
+    }
+
+    /// Calls `cache.find` and keeps what identifies each neighbor.
+    fn find(
+        cache: &mut NeighborCache,
+        params: &BoidsParams,
+        translations: &[Vector3<f32>],
+        range: f32,
+        stats: &mut StepStats,
+    ) -> Found {
+        let n = translations.len();
+        let (species, informed) = (vec![0; n], vec![false; n]);
+        let attributes = Attributes {
+            species: &species,
+            informed: &informed,
+        };
+        let velocities = vec![Vector3::zeros(); n];
+        cache
+            .find(params, translations, &velocities, attributes, range, stats)
+            .into_iter()
+            .map(|neighbors| {
+                neighbors
+                    .into_iter()
+                    .map(|neighbor| (neighbor.index, neighbor.distance, neighbor.offset))
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn find_once(params: &BoidsParams, translations: &[Vector3<f32>], range: f32) -> Found {
+        let mut cache = NeighborCache::new(params.neighbor_index);
+        find(
+            &mut cache,
+            params,
+            translations,
+            range,
+            &mut StepStats::default(),
+        )
+    }
+
+    /// Random boids in the box from -0.5 to 0.5, plus boids exactly `range`
+    /// apart along an axis, and across the faces of the box.
+    fn flock(rng: &mut StdRng, n: usize, range: f32) -> Vec<Vector3<f32>> {
+        let mut translations: Vec<_> = (0..n)
+            .map(|_| Vector3::new(rng.gen(), rng.gen(), rng.gen()) - Vector3::repeat(0.5))
+            .collect();
+        for k in 0..3 {
+            translations.push(Vector3::new(k as f32 * range, 0.0, 0.0));
+        }
+        translations.push(Vector3::new(0.0, 0.5 - range / 2.0, 0.0));
+        translations.push(Vector3::new(0.0, -0.5 + range / 2.0, 0.0));
+        translations
+    }
+
+    #[test]
+    fn within_range_is_inclusive() {
+        let range = 0.25;
+        let point = Vector3::new(0.1, -0.2, 0.3);
+        let mut translations = Vec::new();
+        for axis in 0..3 {
+            for &sign in &[-1.0, 1.0] {
+                let mut offset = Vector3::zeros();
+                offset[axis] = sign * range;
+                translations.push(point + offset);
+                offset[axis] = sign * range * 1.001;
+                translations.push(point + offset);
+            }
+        }
+        translations.push(point + Vector3::new(0.15, 0.2, 0.0));
+        let mut expected: Vec<_> = (0..translations.len())
+            .filter(|&j| in_range(&translations, &point, j, range))
+            .collect();
+        assert!(expected.len() >= 6);
+
+        for &kind in &KINDS {
+            let mut index = kind.create();
+            index.build(&translations, range);
+            let mut found = Vec::new();
+            index.within_range(&translations, &point, range, &mut found);
+            found.sort_unstable();
+            expected.sort_unstable();
+            assert_eq!(found, expected, "{:?}", kind);
+        }
+    }
+
+    #[test]
+    fn indices_agree() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let range = 0.25;
+        for &periodic in &[false, true] {
+            for _ in 0..5 {
+                let translations = flock(&mut rng, 200, range);
+                let expected = find_once(
+                    &params(NeighborIndexKind::BruteForce, periodic, 0.0),
+                    &translations,
+                    range,
+                );
+                // The boids placed exactly `range` apart find each other.
+                let on_edge = translations.len() - 5;
+                assert!(expected[on_edge]
+                    .iter()
+                    .any(|&(j, distance, _)| j == on_edge + 1 && distance == range));
+                if periodic {
+                    let across = translations.len() - 2;
+                    assert!(expected[across]
+                        .iter()
+                        .any(|&(j, distance, _)| j == across + 1 && distance == range));
+                }
+                for &kind in &KINDS {
+                    let found = find_once(&params(kind, periodic, 0.0), &translations, range);
+                    assert_eq!(found, expected, "{:?}, periodic: {}", kind, periodic);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn indices_agree_with_skin() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let (range, skin) = (0.25, 0.05);
+        for &periodic in &[false, true] {
+            let start = flock(&mut rng, 200, range);
+            let steps: Vec<Vec<_>> = (0..4)
+                .map(|step| {
+                    // Moves of less than half the skin, so the index is kept.
+                    let mut rng = StdRng::seed_from_u64(step);
+                    start
+                        .iter()
+                        .map(|translation| {
+                            let direction = Vector3::new(rng.gen(), rng.gen(), rng.gen())
+                                - Vector3::repeat(0.5);
+                            translation + direction * (skin / 2.0)
+                        })
+                        .collect()
+                })
+                .collect();
+
+            for &kind in &KINDS {
+                let skinned = params(kind, periodic, skin);
+                let exact = params(NeighborIndexKind::BruteForce, periodic, 0.0);
+                let mut cache = NeighborCache::new(kind);
+                let mut stats = StepStats::default();
+                find(&mut cache, &skinned, &start, range, &mut stats);
+                for translations in &steps {
+                    let found = find(&mut cache, &skinned, translations, range, &mut stats);
+                    let expected = find_once(&exact, translations, range);
+                    assert_eq!(found, expected, "{:?}, periodic: {}", kind, periodic);
+                }
+                assert_eq!(stats.index_rebuilds, 1);
+            }
+        }
+    }
+
+    /// Indices of the neighbors of every boid of `translations`, along the x
+    /// axis, for every index.
+    fn picks(translations: &[f32], range: f32, max_neighbors: usize) -> Vec<Vec<usize>> {
+        let translations: Vec<_> = translations
+            .iter()
+            .map(|&x| Vector3::new(x, 0.0, 0.0))
+            .collect();
+        let mut picks = None;
+        for &kind in &KINDS {
+            let params = BoidsParams {
+                max_neighbors,
+                ..params(kind, false, 0.0)
+            };
+            let found: Vec<Vec<_>> = find_once(&params, &translations, range)
+                .into_iter()
+                .map(|neighbors| neighbors.into_iter().map(|(j, _, _)| j).collect())
+                .collect();
+            if let Some(picks) = &picks {
+                assert_eq!(&found, picks, "{:?}", kind);
+            }
+            picks = Some(found);
+        }
+        picks.unwrap()
+    }
+
+    #[test]
+    fn max_neighbors_excludes_self() {
+        // Two boids at the same point pick each other, not themselves.
+        assert_eq!(picks(&[0.0, 0.0], 0.1, 1), vec![vec![1], vec![0]]);
+    }
+
+    #[test]
+    fn max_neighbors_breaks_ties_by_index() {
+        // Boid 0 is as far from 1 as from 2 and picks 1. Boids 1 and 2 pick
+        // their nearer partners 3 and 4, so 0 and 2 are not neighbors.
+        let found = picks(&[0.0, 0.125, -0.125, 0.1875, -0.1875], 0.15, 1);
+        assert_eq!(found[0], vec![1]);
+        assert_eq!(found[1], vec![3, 0]);
+        assert_eq!(found[2], vec![4]);
+    }
+
+    #[test]
+    fn max_neighbors_zero_picks_nothing() {
+        let found = picks(&[0.0, 0.0625, 0.125], 0.2, 0);
+        assert!(found.iter().all(|neighbors| neighbors.is_empty()));
+    }
+
+    #[test]
+    fn max_neighbors_above_candidates_picks_all() {
+        let translations = [0.0, 0.0625, 0.125, 0.25, 1.0];
+        let all = picks(&translations, 0.2, std::usize::MAX);
+        assert_eq!(all[0], vec![1, 2]);
+        assert_eq!(all[4], Vec::<usize>::new());
+        // Boid 2 has the most candidates: 0, 1 and 3.
+        assert_eq!(picks(&translations, 0.2, 3), all);
+    }
+
+    #[test]
+    fn max_neighbors_is_a_symmetric_union() {
+        // Boid 0 picks 1, which picks 2: boid 1 ends up with two neighbors,
+        // and 0 and 2 are within range but not neighbors.
+        let found = picks(&[0.0, 0.125, 0.1875], 0.2, 1);
+        assert_eq!(found, vec![vec![1], vec![2, 0], vec![1]]);
+    }
+
+    #[test]
+    fn range_is_inclusive() {
+        let found = picks(&[0.0, 0.25, -0.25 - 1e-6], 0.25, std::usize::MAX);
+        assert_eq!(found[0], vec![1]);
+    }
+}
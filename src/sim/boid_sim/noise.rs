@@ -0,0 +1,75 @@
+use nalgebra::{Rotation3, Unit, Vector3};
+use rand::{distributions::StandardNormal, Rng};
+use serde::{Deserialize, Serialize};
+
+/// Random perturbations of the boids' velocities, drawn from the simulation
+/// RNG after every step, as in the model of Vicsek et al., "Novel type of
+/// phase transition in a system of self-driven particles" (1995).
+///
+/// Without noise, the update is deterministic, and a flock only ever orders.
+/// Raising `angular` makes it go through the order–disorder transition, which
+/// `BoidsSimulation::polarization` measures.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NoiseParams {
+    /// Angular noise on headings, the η of Vicsek et al., in `[0, 1]`. Each
+    /// step, a boid's heading is redrawn uniformly within the cone around it
+    /// that covers this fraction of the sphere: 0 leaves headings alone, 1
+    /// draws them uniformly at random.
+    pub angular: f32,
+    /// Standard deviation of a white-noise acceleration on each axis, in
+    /// units per second to the power 1.5: over one second, it spreads
+    /// velocities by about this much. Speed limits still apply.
+    pub force: f32,
+}
+
+impl Default for NoiseParams {
+    fn default() -> NoiseParams {
+        NoiseParams {
+            angular: 0.0,
+            force: 0.0,
+        }
+    }
+}
+
+impl NoiseParams {
+    pub fn is_enabled(&self) -> bool {
+        self.angular > 0.0 || self.force > 0.0
+    }
+
+    /// Random change of velocity over a step of `dt` seconds, from the force
+    /// noise.
+    pub(crate) fn kick<R: Rng + ?Sized>(&self, dt: f32, rng: &mut R) -> Vector3<f32> {
+        if self.force <= 0.0 {
+            return Vector3::zeros();
+        }
+        let sample = |rng: &mut R| rng.sample::<f64, _>(StandardNormal) as f32;
+        Vector3::new(sample(rng), sample(rng), sample(rng)) * self.force * dt.sqrt()
+    }
+
+    /// Turns `velocity` by the angular noise, keeping its speed.
+    pub(crate) fn turn<R: Rng + ?Sized>(
+        &self,
+        velocity: &Vector3<f32>,
+        rng: &mut R,
+    ) -> Vector3<f32> {
+        let heading = match Unit::try_new(*velocity, 0.0) {
+            Some(heading) if self.angular > 0.0 => heading,
+            _ => return *velocity,
+        };
+        // Uniform on the spherical cap: the cosine of the angle to the
+        // heading is uniform over the height of the cap.
+        let cos_angle = 1.0 - 2.0 * self.angular.min(1.0) * rng.gen::<f32>();
+        let angle = cos_angle.clamp(-1.0, 1.0).acos();
+        let around = std::f32::consts::PI * 2.0 * rng.gen::<f32>();
+
+        let any = if heading.x.abs() < 0.9 {
+            Vector3::x()
+        } else {
+            Vector3::y()
+        };
+        let axis = Unit::new_normalize(heading.cross(&any));
+        let axis = Rotation3::from_axis_angle(&heading, around) * axis;
+        Rotation3::from_axis_angle(&axis, angle) * velocity
+    }
+}
@@ -0,0 +1,143 @@
+use nalgebra::Vector3;
+use serde::{de, Deserialize, Deserializer, Serialize};
+
+/// Static geometry boids steer around.
+///
+/// In scenario files, obstacles are `[[params.obstacles]]` tables tagged with
+/// their shape, e.g. `shape = "sphere"`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "shape", rename_all = "snake_case")]
+pub enum Obstacle {
+    Sphere {
+        center: Vector3<f32>,
+        radius: f32,
+    },
+    /// An axis-aligned box.
+    #[serde(rename = "box")]
+    Aabb {
+        min: Vector3<f32>,
+        max: Vector3<f32>,
+    },
+    /// An infinite plane. Everything behind it, opposite to `normal`, is
+    /// solid.
+    Plane {
+        point: Vector3<f32>,
+        #[serde(deserialize_with = "deserialize_normal")]
+        normal: Vector3<f32>,
+    },
+    /// The points within `radius` of the segment from `a` to `b`.
+    Capsule {
+        a: Vector3<f32>,
+        b: Vector3<f32>,
+        radius: f32,
+    },
+}
+
+fn deserialize_normal<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Vector3<f32>, D::Error> {
+    let normal = Vector3::<f32>::deserialize(deserializer)?;
+    if !normal.iter().all(|x| x.is_finite()) {
+        return Err(de::Error::custom("plane normals must be finite"));
+    }
+    if normal.norm() == 0.0 {
+        return Err(de::Error::custom("plane normals must not be zero"));
+    }
+    Ok(normal)
+}
+
+impl Obstacle {
+    /// Signed distance from `point` to the surface of the obstacle, negative
+    /// inside of it.
+    pub fn distance(&self, point: &Vector3<f32>) -> f32 {
+        match self {
+            Obstacle::Sphere { center, radius } => (point - center).norm() - radius,
+            Obstacle::Aabb { min, max } => {
+                let center = (min + max) / 2.0;
+                let q = (point - center).abs() - (max - min) / 2.0;
+                let outside = q.map(|x| x.max(0.0)).norm();
+                let inside = q.max().min(0.0);
+                outside + inside
+            }
+            Obstacle::Plane {
+                point: origin,
+                normal,
+            } => (point - origin).dot(&normal.normalize()),
+            Obstacle::Capsule { a, b, radius } => {
+                (point - closest_on_segment(a, b, point)).norm() - radius
+            }
+        }
+    }
+
+    /// Unit normal of the surface nearest to `point`, pointing out of the
+    /// obstacle.
+    pub fn normal(&self, point: &Vector3<f32>) -> Vector3<f32> {
+        let normal = match self {
+            Obstacle::Sphere { center, .. } => point - center,
+            Obstacle::Aabb { min, max } => {
+                let clamped = point.zip_zip_map(min, max, |x, min, max| x.max(min).min(max));
+                if clamped != *point {
+                    point - clamped
+                } else {
+                    // Inside: push out through the nearest face.
+                    let center = (min + max) / 2.0;
+                    let q = (point - center).abs() - (max - min) / 2.0;
+                    let axis = q.imax();
+                    let mut normal = Vector3::zeros();
+                    normal[axis] = (point[axis] - center[axis]).signum();
+                    normal
+                }
+            }
+            Obstacle::Plane { normal, .. } => *normal,
+            Obstacle::Capsule { a, b, .. } => point - closest_on_segment(a, b, point),
+        };
+
+        if normal.norm() > 0.0 {
+            normal.normalize()
+        } else {
+            Vector3::y()
+        }
+    }
+}
+
+fn closest_on_segment(a: &Vector3<f32>, b: &Vector3<f32>, point: &Vector3<f32>) -> Vector3<f32> {
+    let ab = b - a;
+    let length_2 = ab.norm_squared();
+    if length_2 == 0.0 {
+        return *a;
+    }
+    let t = ((point - a).dot(&ab) / length_2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Deserialize)]
+    struct Config {
+        obstacle: Obstacle,
+    }
+
+    fn parse(normal: &str) -> Result<Obstacle, toml::de::Error> {
+        let source = format!(
+            "obstacle = {{ shape = \"plane\", point = [0.0, 0.0, 0.0], normal = {} }}",
+            normal
+        );
+        toml::from_str::<Config>(&source).map(|config| config.obstacle)
+    }
+
+    #[test]
+    fn plane_normals_are_normalized() {
+        let plane = parse("[0.0, 2.0, 0.0]").unwrap();
+        assert_eq!(plane.distance(&Vector3::new(1.0, 3.0, 0.0)), 3.0);
+        assert_eq!(plane.normal(&Vector3::zeros()), Vector3::y());
+    }
+
+    #[test]
+    fn plane_rejects_bad_normals() {
+        assert!(parse("[0.0, 0.0, 0.0]").is_err());
+        assert!(parse("[0.0, nan, 0.0]").is_err());
+        assert!(parse("[inf, 0.0, 0.0]").is_err());
+    }
+}
@@ -0,0 +1,43 @@
+//! Per-boid loops, run on the rayon thread pool when the `parallel` feature
+//! is enabled and sequentially otherwise.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+/// Collects `f(i)` for every boid index `i` in `0..n`.
+pub(crate) fn map<T, F>(n: usize, f: F) -> Vec<T>
+where
+    T: Send,
+    F: Fn(usize) -> T + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        (0..n).into_par_iter().map(f).collect()
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        (0..n).map(f).collect()
+    }
+}
+
+/// Calls `f(i, item)` for every item of `items`.
+pub(crate) fn for_each_mut<T, F>(items: &mut [T], f: F)
+where
+    T: Send,
+    F: Fn(usize, &mut T) + Send + Sync,
+{
+    #[cfg(feature = "parallel")]
+    {
+        items
+            .par_iter_mut()
+            .enumerate()
+            .for_each(|(i, item)| f(i, item));
+    }
+    #[cfg(not(feature = "parallel"))]
+    {
+        items
+            .iter_mut()
+            .enumerate()
+            .for_each(|(i, item)| f(i, item));
+    }
+}
@@ -0,0 +1,224 @@
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Which boids follow a `Path`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Followers {
+    All,
+    /// Informed boids only. See `LeaderParams`.
+    Informed,
+}
+
+impl Default for Followers {
+    fn default() -> Followers {
+        Followers::All
+    }
+}
+
+/// A route for boids to follow, through a list of waypoints, as written in
+/// `[[params.paths]]` tables of scenario files. See `PathFollowing`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Path {
+    pub waypoints: Vec<Vector3<f32>>,
+    /// Whether the path loops from the last waypoint back to the first.
+    pub closed: bool,
+    /// Whether the path is a Catmull-Rom spline through the waypoints,
+    /// instead of straight segments between them.
+    pub spline: bool,
+    /// Half width of the path. Boids heading the right way that are predicted
+    /// to stay this close to it steer toward it less.
+    pub radius: f32,
+    /// Distance ahead, along their heading and along the path, at which boids
+    /// aim.
+    pub lookahead: f32,
+    /// Boids slow down within this distance of the end of an open path.
+    pub arrival_radius: f32,
+    pub followers: Followers,
+    /// Species of the boids that follow the path, by index. Empty for every
+    /// species.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub species: Vec<usize>,
+    /// Color of the path when drawn, as RGB in `[0, 1]`.
+    pub color: [f32; 3],
+}
+
+impl Default for Path {
+    fn default() -> Path {
+        Path {
+            waypoints: Vec::new(),
+            closed: false,
+            spline: false,
+            radius: 0.05,
+            lookahead: 0.1,
+            arrival_radius: 0.2,
+            followers: Followers::All,
+            species: Vec::new(),
+            color: [0.3, 0.8, 0.3],
+        }
+    }
+}
+
+/// Number of segments spline paths are drawn and followed with, between two
+/// waypoints.
+const SPLINE_SEGMENTS: usize = 16;
+
+impl Path {
+    /// Whether boids of species `species`, informed or not, follow the path.
+    pub fn is_followed_by(&self, species: usize, informed: bool) -> bool {
+        (self.followers == Followers::All || informed)
+            && (self.species.is_empty() || self.species.contains(&species))
+    }
+
+    /// The polyline the path follows. Closed paths end with their first
+    /// point.
+    pub fn points(&self) -> Vec<Vector3<f32>> {
+        let waypoints = &self.waypoints;
+        let n = waypoints.len();
+        if n < 2 {
+            return waypoints.clone();
+        }
+
+        let mut points = Vec::new();
+        let segments = if self.closed { n } else { n - 1 };
+        if !self.spline {
+            points.extend(waypoints.iter().cloned());
+        } else {
+            let waypoint = |i: isize| {
+                if self.closed {
+                    waypoints[i.rem_euclid(n as isize) as usize]
+                } else {
+                    waypoints[i.clamp(0, n as isize - 1) as usize]
+                }
+            };
+            for segment in 0..segments as isize {
+                let (p0, p1, p2, p3) = (
+                    waypoint(segment - 1),
+                    waypoint(segment),
+                    waypoint(segment + 1),
+                    waypoint(segment + 2),
+                );
+                for k in 0..SPLINE_SEGMENTS {
+                    let t = k as f32 / SPLINE_SEGMENTS as f32;
+                    points.push(catmull_rom(p0, p1, p2, p3, t));
+                }
+            }
+            if !self.closed {
+                points.push(waypoints[n - 1]);
+            }
+        }
+        if self.closed {
+            points.push(points[0]);
+        }
+        points
+    }
+}
+
+fn catmull_rom(
+    p0: Vector3<f32>,
+    p1: Vector3<f32>,
+    p2: Vector3<f32>,
+    p3: Vector3<f32>,
+    t: f32,
+) -> Vector3<f32> {
+    let (t2, t3) = (t * t, t * t * t);
+    (p1 * 2.0
+        + (p2 - p0) * t
+        + (p0 * 2.0 - p1 * 5.0 + p2 * 4.0 - p3) * t2
+        + (p1 * 3.0 - p0 - p2 * 3.0 + p3) * t3)
+        * 0.5
+}
+
+/// A point of a polyline, as found by `Polyline::project`.
+pub(crate) struct Projection {
+    /// Distance from the start of the polyline, along it.
+    pub along: f32,
+    pub point: Vector3<f32>,
+    /// Direction of the polyline at `point`.
+    pub tangent: Vector3<f32>,
+}
+
+/// The points of a `Path`, with the distance along the path of each.
+pub(crate) struct Polyline {
+    points: Vec<Vector3<f32>>,
+    along: Vec<f32>,
+    closed: bool,
+}
+
+impl Polyline {
+    pub fn new(path: &Path) -> Polyline {
+        let points = path.points();
+        let mut along = Vec::with_capacity(points.len());
+        let mut length = 0.0;
+        for (i, point) in points.iter().enumerate() {
+            if i > 0 {
+                length += (point - points[i - 1]).norm();
+            }
+            along.push(length);
+        }
+        Polyline {
+            points,
+            along,
+            closed: path.closed,
+        }
+    }
+
+    pub fn length(&self) -> f32 {
+        self.along.last().cloned().unwrap_or(0.0)
+    }
+
+    /// The point of the polyline nearest to `point`.
+    pub fn project(&self, point: &Vector3<f32>) -> Option<Projection> {
+        let mut best: Option<(f32, Projection)> = None;
+        for (i, segment) in self.points.windows(2).enumerate() {
+            let (a, b) = (segment[0], segment[1]);
+            let ab = b - a;
+            let length_2 = ab.norm_squared();
+            if length_2 == 0.0 {
+                continue;
+            }
+            let t = ((point - a).dot(&ab) / length_2).clamp(0.0, 1.0);
+            let closest = a + ab * t;
+            let distance_2 = (point - closest).norm_squared();
+            if best.as_ref().map(|(d, _)| distance_2 < *d).unwrap_or(true) {
+                best = Some((
+                    distance_2,
+                    Projection {
+                        along: self.along[i] + (self.along[i + 1] - self.along[i]) * t,
+                        point: closest,
+                        tangent: ab / length_2.sqrt(),
+                    },
+                ));
+            }
+        }
+        best.map(|(_, projection)| projection)
+    }
+
+    /// The point `along` from the start of the polyline. Closed polylines
+    /// wrap around; open ones end at their ends.
+    pub fn point_at(&self, along: f32) -> Vector3<f32> {
+        let length = self.length();
+        let along = if self.closed && length > 0.0 {
+            along.rem_euclid(length)
+        } else {
+            along.clamp(0.0, length)
+        };
+        let i = match self.along.iter().position(|&a| a > along) {
+            Some(0) => return self.points[0],
+            Some(i) => i,
+            None => return *self.points.last().unwrap(),
+        };
+        let (a0, a1) = (self.along[i - 1], self.along[i]);
+        let t = (along - a0) / (a1 - a0);
+        self.points[i - 1].lerp(&self.points[i], t)
+    }
+
+    pub fn end(&self) -> Option<Vector3<f32>> {
+        if self.closed {
+            None
+        } else {
+            self.points.last().cloned()
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+
+/// Part of the space around a boid in which it perceives its neighbors: a
+/// cone around its heading, minus a blind cone behind it.
+///
+/// Perception is not symmetric: a boid may see a neighbor that doesn't see
+/// it, in which case only the first one reacts.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Perception {
+    /// Angle between the heading and the edge of the cone, in degrees. 180
+    /// sees all around.
+    pub half_angle: f32,
+    /// Angle between the rear direction and the edge of the blind cone, in
+    /// degrees. 0 leaves no blind spot.
+    pub blind_spot: f32,
+}
+
+impl Default for Perception {
+    fn default() -> Perception {
+        Perception {
+            half_angle: 180.0,
+            blind_spot: 0.0,
+        }
+    }
+}
+
+impl Perception {
+    pub fn new(half_angle: f32, blind_spot: f32) -> Perception {
+        Perception {
+            half_angle,
+            blind_spot,
+        }
+    }
+
+    pub fn is_omnidirectional(&self) -> bool {
+        self.half_angle >= 180.0 && self.blind_spot <= 0.0
+    }
+
+    /// Whether a boid moving at `velocity` sees a neighbor at `offset` from
+    /// it. Boids that don't move, and neighbors at the same position, are
+    /// always seen.
+    pub fn sees(&self, velocity: &Vector3<f32>, offset: &Vector3<f32>) -> bool {
+        let norms = velocity.norm() * offset.norm();
+        if norms == 0.0 {
+            return true;
+        }
+        let cos = velocity.dot(offset) / norms;
+        if cos < self.half_angle.to_radians().cos() {
+            return false;
+        }
+        self.blind_spot <= 0.0 || -cos < self.blind_spot.to_radians().cos()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Offset at `degrees` from the heading, which is along x.
+    fn at(degrees: f32) -> Vector3<f32> {
+        let (sin, cos) = degrees.to_radians().sin_cos();
+        Vector3::new(cos, sin, 0.0) * 2.0
+    }
+
+    #[test]
+    fn sees_up_to_the_cone_edge() {
+        let perception = Perception::new(60.0, 0.0);
+        let velocity = Vector3::x();
+        assert!(perception.sees(&velocity, &at(0.0)));
+        assert!(perception.sees(&velocity, &at(59.9)));
+        assert!(perception.sees(&velocity, &at(-59.9)));
+        assert!(!perception.sees(&velocity, &at(60.1)));
+        assert!(!perception.sees(&velocity, &at(180.0)));
+    }
+
+    #[test]
+    fn blind_spot_starts_at_its_edge() {
+        let perception = Perception::new(180.0, 30.0);
+        let velocity = Vector3::x();
+        assert!(perception.sees(&velocity, &at(149.9)));
+        assert!(perception.sees(&velocity, &at(-149.9)));
+        assert!(!perception.sees(&velocity, &at(150.1)));
+        assert!(!perception.sees(&velocity, &at(180.0)));
+    }
+
+    #[test]
+    fn no_blind_spot_sees_straight_behind() {
+        let velocity = Vector3::x();
+        assert!(Perception::default().sees(&velocity, &-velocity));
+        assert!(Perception::new(180.0, 0.0).sees(&velocity, &at(180.0)));
+    }
+
+    #[test]
+    fn still_boids_and_overlapping_neighbors_are_seen() {
+        let perception = Perception::new(10.0, 90.0);
+        assert!(perception.sees(&Vector3::zeros(), &at(180.0)));
+        assert!(perception.sees(&Vector3::x(), &Vector3::zeros()));
+    }
+}
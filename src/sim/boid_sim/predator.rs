@@ -0,0 +1,263 @@
+use super::{Boid, Boundary, Flock, Neighbor};
+use nalgebra::{Rotation3, Unit, Vector3};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+/// How predators pick the boid they chase.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PredatorTarget {
+    /// The nearest boid, picked again at every step.
+    Nearest,
+    /// The boid farthest from its nearest neighbor, chased until it is caught
+    /// or removed. Boids without neighbors within perception range are the
+    /// most isolated; ties go to the nearest boid.
+    MostIsolated,
+}
+
+impl Default for PredatorTarget {
+    fn default() -> PredatorTarget {
+        PredatorTarget::Nearest
+    }
+}
+
+/// Predators hunting the flock, and how boids react to them. Predators are
+/// spawned with the simulation, and are not boids: they don't follow the
+/// steering rules.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PredatorParams {
+    /// Number of predators spawned with the simulation.
+    pub count: usize,
+    /// Predators are spawned in a sphere of radius `spawn_radius` around
+    /// `spawn_center`.
+    pub spawn_center: Vector3<f32>,
+    pub spawn_radius: f32,
+    pub target: PredatorTarget,
+    /// Speed of predators while they rest.
+    pub min_speed: f32,
+    /// Speed of predators while they hunt.
+    pub max_speed: f32,
+    /// Largest angular speed of predators, in degrees per second.
+    pub turn_rate: f32,
+    /// Predators catch the boids that come this close.
+    pub capture_range: f32,
+    /// Time a predator rests after a capture before hunting again, in
+    /// seconds.
+    pub rest_time: f32,
+    /// Boids react to predators within this distance: see the `flee` and
+    /// `flash_expansion` rules.
+    pub detection_range: f32,
+    /// Speed limit of boids that detect a predator, instead of `max_speed`
+    /// when it is higher.
+    pub burst_speed: f32,
+}
+
+impl Default for PredatorParams {
+    fn default() -> PredatorParams {
+        PredatorParams {
+            count: 0,
+            spawn_center: Vector3::new(0.5, 0.0, 0.0),
+            spawn_radius: 0.05,
+            target: PredatorTarget::Nearest,
+            min_speed: 0.05,
+            max_speed: 0.26,
+            turn_rate: 120.0,
+            capture_range: 0.01,
+            rest_time: 2.0,
+            detection_range: 0.2,
+            burst_speed: 0.22,
+        }
+    }
+}
+
+/// State of a single predator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Predator {
+    pub translation: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Id of the boid being chased.
+    pub target: Option<usize>,
+    /// Time left before the predator hunts again, in seconds.
+    pub rest: f32,
+    /// Number of boids caught by the predator.
+    pub captures: usize,
+}
+
+/// A boid caught by a predator.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Capture {
+    /// Step during which the boid was caught, counting from 1.
+    pub step: u64,
+    /// Index of the predator.
+    pub predator: usize,
+    /// The boid, as it was when caught.
+    pub boid: Boid,
+}
+
+impl Predator {
+    pub fn new(translation: Vector3<f32>, velocity: Vector3<f32>) -> Predator {
+        Predator {
+            translation,
+            velocity,
+            target: None,
+            rest: 0.0,
+            captures: 0,
+        }
+    }
+
+    /// Spawns `params.count` predators, heading in random directions.
+    pub fn spawn<R: Rng + ?Sized>(params: &PredatorParams, rng: &mut R) -> Vec<Predator> {
+        let speed = params.min_speed.min(params.max_speed);
+        Boid::generate_sphere(params.count, params.spawn_radius, speed, speed, rng)
+            .into_iter()
+            .map(|boid| Predator::new(params.spawn_center + boid.translation, boid.velocity))
+            .collect()
+    }
+
+    pub fn is_hunting(&self) -> bool {
+        self.rest <= 0.0
+    }
+
+    /// Picks the boid to chase. `neighbors` are the neighbors of the boids of
+    /// `flock`, sorted by distance.
+    pub(crate) fn pick_target(
+        &mut self,
+        params: &PredatorParams,
+        boundary: &Boundary,
+        flock: &Flock,
+        neighbors: &[Vec<Neighbor>],
+    ) {
+        if !self.is_hunting() {
+            self.target = None;
+            return;
+        }
+        if params.target == PredatorTarget::MostIsolated
+            && self.target.and_then(|id| flock.index_of(id)).is_some()
+        {
+            return;
+        }
+
+        let distance = |i: usize| {
+            boundary
+                .offset(&self.translation, &flock.translations()[i])
+                .norm()
+        };
+        let isolation = |i: usize| {
+            neighbors
+                .get(i)
+                .and_then(|neighbors| neighbors.first())
+                .map_or(std::f32::INFINITY, |neighbor| neighbor.distance)
+        };
+        let key = |i: usize| match params.target {
+            PredatorTarget::Nearest => (0.0, distance(i)),
+            PredatorTarget::MostIsolated => (-isolation(i), distance(i)),
+        };
+
+        self.target = (0..flock.len())
+            .min_by(|&a, &b| {
+                key(a)
+                    .partial_cmp(&key(b))
+                    .unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .map(|i| flock.ids()[i]);
+    }
+
+    /// Turns toward the target at most at `params.turn_rate`, then moves for
+    /// `dt` seconds.
+    pub(crate) fn step(
+        &mut self,
+        params: &PredatorParams,
+        boundary: &Boundary,
+        flock: &Flock,
+        dt: f32,
+    ) {
+        self.rest = (self.rest - dt).max(0.0);
+
+        let target = self
+            .target
+            .and_then(|id| flock.index_of(id))
+            .map(|i| boundary.offset(&self.translation, &flock.translations()[i]));
+        let heading =
+            Unit::try_new(self.velocity, 0.0).unwrap_or_else(|| Unit::new_unchecked(Vector3::x()));
+        let heading = match target.and_then(|offset| Unit::try_new(offset, 0.0)) {
+            Some(wanted) => {
+                let angle = heading.angle(&wanted);
+                let max_angle = params.turn_rate.to_radians() * dt;
+                if angle <= max_angle {
+                    wanted
+                } else {
+                    // Not `slerp`, which turns away from vectors more than a
+                    // right angle apart.
+                    let axis = Unit::try_new(heading.cross(&wanted), 1e-6)
+                        .or_else(|| Unit::try_new(heading.cross(&Vector3::y()), 1e-6))
+                        .unwrap_or_else(Vector3::z_axis);
+                    Rotation3::from_axis_angle(&axis, max_angle) * heading
+                }
+            }
+            None => heading,
+        };
+
+        let speed = if self.is_hunting() {
+            params.max_speed
+        } else {
+            params.min_speed
+        };
+        self.velocity = heading.into_inner() * speed;
+        self.translation += self.velocity * dt;
+        boundary.constrain(&mut self.translation, &mut self.velocity);
+    }
+
+    /// Index of the boid of `flock` the predator catches, if any: the nearest
+    /// one within `params.capture_range`.
+    pub(crate) fn catch(
+        &self,
+        params: &PredatorParams,
+        boundary: &Boundary,
+        flock: &Flock,
+    ) -> Option<usize> {
+        if !self.is_hunting() {
+            return None;
+        }
+        flock
+            .translations()
+            .iter()
+            .map(|translation| boundary.offset(&self.translation, translation).norm())
+            .enumerate()
+            .filter(|&(_, distance)| distance <= params.capture_range)
+            .min_by(|a, b| a.1.partial_cmp(&b.1).unwrap_or(std::cmp::Ordering::Equal))
+            .map(|(i, _)| i)
+    }
+}
+
+/// The predators within `params.detection_range` of a boid at `translation`,
+/// nearest first.
+pub(crate) fn detect_predators(
+    params: &PredatorParams,
+    boundary: &Boundary,
+    predators: &[Predator],
+    translation: &Vector3<f32>,
+) -> Vec<Neighbor> {
+    let mut detected: Vec<_> = predators
+        .iter()
+        .enumerate()
+        .map(|(index, predator)| {
+            let offset = boundary.offset(translation, &predator.translation);
+            Neighbor {
+                index,
+                offset,
+                species: std::usize::MAX,
+                informed: false,
+                distance: offset.norm(),
+                velocity: predator.velocity,
+            }
+        })
+        .filter(|predator| predator.distance <= params.detection_range)
+        .collect();
+    detected.sort_by(|a, b| {
+        a.distance
+            .partial_cmp(&b.distance)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    detected
+}
@@ -0,0 +1,772 @@
+use super::{BoidsParams, BoundaryMode, FlowEffect, Path, Perception, Polyline, ResponseCurve};
+use nalgebra::{Unit, Vector3};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// Rate, per second, at which `alignment_strength` applies: a boid turns by
+/// that fraction of the way toward its neighbors' heading every 1/60 of a
+/// second, whatever the timestep.
+const ALIGNMENT_RATE: f32 = 60.0;
+
+/// A neighbor of a boid, as seen from that boid.
+#[derive(Clone, Debug)]
+pub struct Neighbor {
+    /// Index of the neighbor in the flock.
+    pub index: usize,
+    /// Vector from the boid to the neighbor.
+    pub offset: Vector3<f32>,
+    /// Species of the neighbor. Unused for predators.
+    pub species: usize,
+    /// Whether the neighbor is informed. False for predators.
+    pub informed: bool,
+    pub distance: f32,
+    pub velocity: Vector3<f32>,
+}
+
+/// A boid and its neighborhood, as seen by steering rules.
+pub struct Agent<'a> {
+    /// Index of the boid in the flock.
+    pub index: usize,
+    pub species: usize,
+    /// Whether the boid is informed. See `LeaderParams`.
+    pub informed: bool,
+    pub translation: Vector3<f32>,
+    pub velocity: Vector3<f32>,
+    /// Time since the simulation started, in seconds.
+    pub time: f32,
+    /// Neighbors within the perception range of the pipeline, which is the
+    /// largest `range` of its rules, and within the perception cone of the
+    /// rule.
+    pub neighbors: &'a [Neighbor],
+    /// Predators the boid detects, within `predators.detection_range`,
+    /// nearest first. Their index is in `BoidsSimulation::predators`.
+    pub predators: &'a [Neighbor],
+}
+
+/// A contribution to the acceleration of boids.
+///
+/// Both hooks return an acceleration, in units per second squared, which the
+/// pipeline scales by the rule's weight. A rule implements either or both.
+///
+/// Hooks may be called from several threads at once, except `prepare`.
+pub trait SteeringRule: Send + Sync {
+    /// Called once before every step, to compute what doesn't depend on the
+    /// boids.
+    fn prepare(&mut self, _params: &BoidsParams) {}
+
+    /// Distance up to which `pair` is called.
+    fn range(&self, _params: &BoidsParams) -> f32 {
+        0.0
+    }
+
+    /// Acceleration of `boid` due to `neighbor`. Called once for every
+    /// neighbor within `range` that `boid` sees; the neighbor gets its own
+    /// call with the roles swapped if it sees `boid`, so the response need
+    /// not be symmetric.
+    fn pair(&self, _params: &BoidsParams, _boid: &Agent, _neighbor: &Neighbor) -> Vector3<f32> {
+        Vector3::zeros()
+    }
+
+    /// Acceleration of `boid` that depends on the boid alone or on its whole
+    /// neighborhood.
+    fn boid(&self, _params: &BoidsParams, _boid: &Agent) -> Vector3<f32> {
+        Vector3::zeros()
+    }
+}
+
+/// A rule of the steering pipeline, as written in `[[params.rules]]` tables
+/// of scenario files.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RuleConfig {
+    /// Name of a built-in rule (`separation`, `cohesion`, `alignment`,
+    /// `attraction`, `avoidance`, `walls`, `flee`, `flash_expansion`,
+    /// `guidance`, `path_following`, `attractors` or `flow`), or of a rule
+    /// added with `register_rule`.
+    pub rule: String,
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+    /// Response curve of the rule. Built-in rules have their own default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub curve: Option<ResponseCurve>,
+    /// Perception cone of the rule, if it differs from
+    /// `BoidsParams::perception`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub perception: Option<Perception>,
+}
+
+fn default_weight() -> f32 {
+    1.0
+}
+
+impl RuleConfig {
+    pub fn new(rule: &str, weight: f32) -> RuleConfig {
+        RuleConfig {
+            rule: rule.to_string(),
+            weight,
+            curve: None,
+            perception: None,
+        }
+    }
+
+    pub fn curve(mut self, curve: ResponseCurve) -> RuleConfig {
+        self.curve = Some(curve);
+        self
+    }
+
+    pub fn perception(mut self, perception: Perception) -> RuleConfig {
+        self.perception = Some(perception);
+        self
+    }
+
+    /// The classic boids rules.
+    pub fn defaults() -> Vec<RuleConfig> {
+        BUILTIN_RULES
+            .iter()
+            .map(|name| RuleConfig::new(name, 1.0))
+            .collect()
+    }
+
+    fn curve_or(&self, scale: f32) -> ResponseCurve {
+        self.curve
+            .clone()
+            .unwrap_or_else(|| ResponseCurve::polynomial(scale, 2.0))
+    }
+}
+
+const BUILTIN_RULES: &[&str] = &[
+    "separation",
+    "cohesion",
+    "alignment",
+    "attraction",
+    "avoidance",
+    "walls",
+    "flee",
+    "flash_expansion",
+    "guidance",
+    "path_following",
+    "attractors",
+    "flow",
+];
+
+/// Boids closer than `separation_range` repel each other. The curve input is
+/// `1 - distance / separation_range`.
+///
+/// With several species, the range and a weight come from the interaction
+/// between the species of the boid and of its neighbor, as do those of
+/// `Cohesion` and `Alignment`.
+pub struct Separation {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Separation {
+    fn range(&self, params: &BoidsParams) -> f32 {
+        params.max_interaction_range(params.separation_range, |i| i.separation_range)
+    }
+
+    fn pair(&self, params: &BoidsParams, boid: &Agent, neighbor: &Neighbor) -> Vector3<f32> {
+        let interaction = params.interaction(boid.species, neighbor.species);
+        let range = interaction.separation_range(params);
+        if range <= 0.0 || neighbor.distance > range {
+            return Vector3::zeros();
+        }
+        let t = (range - neighbor.distance) / range;
+        -neighbor.offset * self.curve.eval(t) * interaction.separation
+    }
+}
+
+/// Boids between `separation_range` and `cohesion_range` attract each other.
+/// The curve input goes from 1 at `separation_range` to 0 at
+/// `cohesion_range`.
+pub struct Cohesion {
+    pub curve: ResponseCurve,
+}
+
+/// Position of `neighbor` in the cohesion band of `boid`, from 1 at the
+/// separation range to 0 at the cohesion range, or `None` outside of the
+/// band.
+fn cohesion_band(params: &BoidsParams, boid: &Agent, neighbor: &Neighbor) -> Option<f32> {
+    let interaction = params.interaction(boid.species, neighbor.species);
+    let separation_range = interaction.separation_range(params);
+    let cohesion_range = interaction.cohesion_range(params);
+    let distance = neighbor.distance;
+    if distance <= separation_range || distance > cohesion_range {
+        return None;
+    }
+    Some((cohesion_range - distance) / (cohesion_range - separation_range))
+}
+
+impl SteeringRule for Cohesion {
+    fn range(&self, params: &BoidsParams) -> f32 {
+        params.max_interaction_range(params.cohesion_range, |i| i.cohesion_range)
+    }
+
+    fn pair(&self, params: &BoidsParams, boid: &Agent, neighbor: &Neighbor) -> Vector3<f32> {
+        match cohesion_band(params, boid, neighbor) {
+            Some(t) => {
+                let weight = params.interaction(boid.species, neighbor.species).cohesion;
+                neighbor.offset * self.curve.eval(t) * weight
+            }
+            None => Vector3::zeros(),
+        }
+    }
+}
+
+/// Boids in the cohesion band turn toward the average heading of their
+/// neighbors, weighted by the curve, by `alignment_strength` every 1/60 of a
+/// second. The curve input
+/// is the same as for `Cohesion`. Informed neighbors weigh
+/// `leaders.influence` times more than the others.
+pub struct Alignment {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Alignment {
+    fn range(&self, params: &BoidsParams) -> f32 {
+        params.max_interaction_range(params.cohesion_range, |i| i.cohesion_range)
+    }
+
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        if params.dt <= 0.0 {
+            return Vector3::zeros();
+        }
+        let mut neighbor_velocity = Vector3::zeros();
+        for neighbor in boid.neighbors {
+            if let Some(t) = cohesion_band(params, boid, neighbor) {
+                let mut weight = params.interaction(boid.species, neighbor.species).alignment;
+                if neighbor.informed {
+                    weight *= params.leaders.influence;
+                }
+                neighbor_velocity += self.curve.eval(t) * weight * neighbor.velocity;
+            }
+        }
+
+        if neighbor_velocity.norm() == 0.0 || boid.velocity.norm() == 0.0 {
+            return Vector3::zeros();
+        }
+
+        let velocity_dir = Unit::new_normalize(boid.velocity);
+        let avg_neighbor_velocity_dir = Unit::new_normalize(neighbor_velocity);
+        // Compounds `alignment_strength` over the 1/60 s periods in the step,
+        // so that boids turn as fast in wall time with any timestep.
+        let strength = params.alignment_strength.clamp(0.0, 1.0);
+        let turn = 1.0 - (1.0 - strength).powf(params.dt * ALIGNMENT_RATE);
+        let wanted_velocity = velocity_dir
+            .slerp(&avg_neighbor_velocity_dir, turn)
+            .into_inner()
+            * boid.velocity.norm();
+        (wanted_velocity - boid.velocity) / params.dt
+    }
+}
+
+/// Boids farther than `attraction_min_range` from `attraction_center` are
+/// pulled toward it. The curve input is the distance past
+/// `attraction_min_range`.
+pub struct Attraction {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Attraction {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let delta = params
+            .boundary
+            .offset(&boid.translation, &params.attraction_center);
+        let dist = delta.norm();
+        if dist < params.attraction_min_range {
+            return Vector3::zeros();
+        }
+        delta.normalize() * self.curve.eval(dist - params.attraction_min_range)
+    }
+}
+
+/// Boids steer around `obstacles` they are heading into, up to
+/// `obstacle_lookahead` ahead, with an acceleration of up to
+/// `avoidance_strength`. The curve input goes from 0 for an obstacle at the
+/// lookahead distance to 1 for an obstacle right in front of the boid.
+pub struct Avoidance {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Avoidance {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let lookahead = params.obstacle_lookahead;
+        let mut acceleration = Vector3::zeros();
+
+        for obstacle in &params.obstacles {
+            if obstacle.distance(&boid.translation) < 0.0 {
+                // Already inside: get out the shortest way.
+                acceleration += obstacle.normal(&boid.translation)
+                    * params.avoidance_strength
+                    * self.curve.eval(1.0);
+                continue;
+            }
+
+            let heading = match Unit::try_new(boid.velocity, 0.0) {
+                Some(heading) if lookahead > 0.0 => heading,
+                _ => continue,
+            };
+
+            // March along the heading, by steps that can't overshoot the
+            // surface, to the first point of contact.
+            let mut t = 0.0;
+            let mut hit = None;
+            for _ in 0..AVOIDANCE_MARCH_STEPS {
+                let point = boid.translation + heading.as_ref() * t;
+                let distance = obstacle.distance(&point);
+                if distance <= lookahead * 1e-3 {
+                    hit = Some((t, point));
+                    break;
+                }
+                t += distance;
+                if t > lookahead {
+                    break;
+                }
+            }
+
+            if let Some((t, point)) = hit {
+                // Turn away from the surface rather than brake.
+                let normal = obstacle.normal(&point);
+                let mut away = normal - heading.as_ref() * normal.dot(&heading);
+                if away.norm() < 1e-6 {
+                    // Head-on: any side will do.
+                    away = heading.cross(&Vector3::y());
+                    if away.norm() < 1e-6 {
+                        away = heading.cross(&Vector3::x());
+                    }
+                }
+                let urgency = 1.0 - t / lookahead;
+                acceleration +=
+                    away.normalize() * params.avoidance_strength * self.curve.eval(urgency);
+            }
+        }
+
+        acceleration
+    }
+}
+
+const AVOIDANCE_MARCH_STEPS: usize = 32;
+
+/// With soft boundaries, boids closer than `boundary.falloff` to a face of
+/// the world box are pushed back inside, with an acceleration of
+/// `boundary.strength` at the face. The curve input is
+/// `1 - distance / falloff` for each face, and keeps growing outside the box.
+pub struct Walls {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Walls {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let boundary = &params.boundary;
+        let mut acceleration = Vector3::zeros();
+        if boundary.mode != BoundaryMode::Soft || boundary.falloff <= 0.0 {
+            return acceleration;
+        }
+
+        for axis in 0..3 {
+            let x = boid.translation[axis];
+            for &(distance, direction) in &[
+                (x - boundary.min[axis], 1.0),
+                (boundary.max[axis] - x, -1.0),
+            ] {
+                if distance < boundary.falloff {
+                    acceleration[axis] += direction
+                        * boundary.strength
+                        * self.curve.eval(1.0 - distance / boundary.falloff);
+                }
+            }
+        }
+
+        acceleration
+    }
+}
+
+/// Boids flee the predators they detect. The curve input goes from 0 at
+/// `predators.detection_range` to 1 at the predator.
+pub struct Flee {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Flee {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let range = params.predators.detection_range;
+        boid.predators
+            .iter()
+            .filter(|predator| predator.distance > 0.0)
+            .map(|predator| {
+                -predator.offset.normalize() * self.curve.eval(1.0 - predator.distance / range)
+            })
+            .sum()
+    }
+}
+
+/// Boids that detect a predator scatter away from their neighbors within
+/// their cohesion range, so that the flock expands around the attack. The
+/// curve input goes from 0 at the cohesion range to 1 at the boid.
+pub struct FlashExpansion {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for FlashExpansion {
+    fn range(&self, params: &BoidsParams) -> f32 {
+        params.max_interaction_range(params.cohesion_range, |i| i.cohesion_range)
+    }
+
+    fn pair(&self, params: &BoidsParams, boid: &Agent, neighbor: &Neighbor) -> Vector3<f32> {
+        if boid.predators.is_empty() || neighbor.distance <= 0.0 {
+            return Vector3::zeros();
+        }
+        let interaction = params.interaction(boid.species, neighbor.species);
+        let range = interaction.cohesion_range(params);
+        if range <= 0.0 || neighbor.distance > range {
+            return Vector3::zeros();
+        }
+        let t = 1.0 - neighbor.distance / range;
+        -neighbor.offset.normalize() * self.curve.eval(t)
+    }
+}
+
+/// Informed boids turn toward the preferred direction of `leaders`: they want
+/// to head between their current heading and the preferred direction, with
+/// `leaders.weight` on the latter. The curve input is the angle between the
+/// heading and the preferred direction, from 0 to 1 for a half turn.
+pub struct Guidance {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Guidance {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        if !boid.informed {
+            return Vector3::zeros();
+        }
+        let leaders = &params.leaders;
+        let preferred = leaders.preferred_direction(&params.boundary, &boid.translation);
+        let (preferred, heading) = match (preferred, Unit::try_new(boid.velocity, 0.0)) {
+            (Some(preferred), Some(heading)) => (preferred, heading),
+            _ => return Vector3::zeros(),
+        };
+
+        let wanted = heading.as_ref() + preferred.as_ref() * leaders.weight;
+        let wanted_velocity = match wanted.try_normalize(1e-6) {
+            Some(wanted) => wanted * boid.velocity.norm(),
+            // Heading away with a weight of 1: turn any way.
+            None => preferred.into_inner() * boid.velocity.norm(),
+        };
+        let t = heading.angle(&preferred) / std::f32::consts::PI;
+        (wanted_velocity - boid.velocity) * self.curve.eval(t)
+    }
+}
+
+/// Boids follow the first of `paths` they are a follower of, after Reynolds:
+/// they aim at the point `lookahead` ahead along the path of where they will
+/// be `lookahead` ahead along their heading, and slow down near the end of
+/// open paths. The curve input is the distance from the path of where they
+/// will be, from 0 on the path to 1 at `radius` and beyond, or 1 for boids
+/// heading back along the path or arriving at its end.
+pub struct PathFollowing {
+    pub curve: ResponseCurve,
+    /// Polylines of `paths`, built by `prepare`.
+    polylines: Vec<Polyline>,
+    paths: Vec<Path>,
+}
+
+impl PathFollowing {
+    pub fn new(curve: ResponseCurve) -> PathFollowing {
+        PathFollowing {
+            curve,
+            polylines: Vec::new(),
+            paths: Vec::new(),
+        }
+    }
+}
+
+impl SteeringRule for PathFollowing {
+    fn prepare(&mut self, params: &BoidsParams) {
+        if self.paths != params.paths {
+            self.polylines = params.paths.iter().map(Polyline::new).collect();
+            self.paths = params.paths.clone();
+        }
+    }
+
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let (i, path) = match params
+            .paths
+            .iter()
+            .enumerate()
+            .find(|(_, path)| path.is_followed_by(boid.species, boid.informed))
+        {
+            Some(found) => found,
+            None => return Vector3::zeros(),
+        };
+        let heading = match Unit::try_new(boid.velocity, 0.0) {
+            Some(heading) => heading,
+            None => return Vector3::zeros(),
+        };
+
+        // Only built here when `prepare` wasn't called.
+        let built;
+        let polyline = match self.polylines.get(i) {
+            Some(polyline) => polyline,
+            None => {
+                built = Polyline::new(path);
+                &built
+            }
+        };
+        let predicted = boid.translation + heading.as_ref() * path.lookahead;
+        let projection = match polyline.project(&predicted) {
+            Some(projection) => projection,
+            None => return Vector3::zeros(),
+        };
+
+        let (_, max_speed) = params.speed_limits(boid.species);
+        let along = projection.along + path.lookahead;
+        let (target, speed, t) = match polyline.end() {
+            Some(end) if along >= polyline.length() => {
+                let distance = params.boundary.offset(&boid.translation, &end).norm();
+                let speed = if path.arrival_radius > 0.0 {
+                    max_speed * (distance / path.arrival_radius).min(1.0)
+                } else {
+                    max_speed
+                };
+                (end, speed, 1.0)
+            }
+            _ => {
+                let distance = (predicted - projection.point).norm();
+                let t = if projection.tangent.dot(heading.as_ref()) < 0.0 || path.radius <= 0.0 {
+                    1.0
+                } else {
+                    (distance / path.radius).min(1.0)
+                };
+                (polyline.point_at(along), max_speed, t)
+            }
+        };
+
+        let wanted_velocity = params
+            .boundary
+            .offset(&boid.translation, &target)
+            .try_normalize(0.0)
+            .map_or_else(Vector3::zeros, |direction| direction * speed);
+        (wanted_velocity - boid.velocity) * self.curve.eval(t)
+    }
+}
+
+/// Boids within range of each of `attractors` are pulled toward it, or
+/// pushed away from it if its strength is negative. The curve input goes from
+/// 0 at the range of the attractor to 1 at the attractor, and is 1 everywhere
+/// for attractors with an infinite range. Attractors with a `falloff` use it
+/// instead of the curve.
+pub struct Attractors {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for Attractors {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let mut acceleration = Vector3::zeros();
+        for attractor in &params.attractors {
+            let position = attractor.position_at(boid.time);
+            let offset = params.boundary.offset(&boid.translation, &position);
+            let distance = offset.norm();
+            if distance == 0.0 || distance <= attractor.min_range || distance > attractor.range {
+                continue;
+            }
+            let t = if attractor.range.is_finite() {
+                1.0 - distance / attractor.range
+            } else {
+                1.0
+            };
+            let curve = attractor.falloff.as_ref().unwrap_or(&self.curve);
+            acceleration += offset / distance * attractor.strength * curve.eval(t);
+        }
+        acceleration
+    }
+}
+
+/// Boids are pushed by the `flows` with a `force` effect. Drifting flows
+/// carry boids without going through the pipeline. The curve input is the
+/// strength of the force, whose direction is kept.
+pub struct FlowForce {
+    pub curve: ResponseCurve,
+}
+
+impl SteeringRule for FlowForce {
+    fn boid(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let force: Vector3<f32> = params
+            .flows
+            .iter()
+            .filter(|flow| flow.effect == FlowEffect::Force)
+            .map(|flow| flow.sample(&boid.translation, boid.time))
+            .sum();
+        let strength = force.norm();
+        if strength == 0.0 {
+            return force;
+        }
+        force / strength * self.curve.eval(strength)
+    }
+}
+
+type RuleFactory = Rc<dyn Fn(&RuleConfig) -> Box<dyn SteeringRule>>;
+
+thread_local! {
+    static CUSTOM_RULES: RefCell<HashMap<String, RuleFactory>> = RefCell::new(HashMap::new());
+}
+
+/// Makes a user-defined rule available to scenarios under `name`. `factory`
+/// creates the rule from its configuration, e.g. to read its curve. An
+/// existing rule with the same name is replaced; built-in rules can't be.
+pub fn register_rule<R, F>(name: &str, factory: F)
+where
+    R: SteeringRule + 'static,
+    F: Fn(&RuleConfig) -> R + 'static,
+{
+    CUSTOM_RULES.with(|rules| {
+        rules.borrow_mut().insert(
+            name.to_string(),
+            Rc::new(move |config: &RuleConfig| Box::new(factory(config)) as Box<dyn SteeringRule>),
+        )
+    });
+}
+
+/// Creates the rule described by `config`, or returns `None` if no rule has
+/// that name.
+pub fn create_rule(config: &RuleConfig) -> Option<Box<dyn SteeringRule>> {
+    let rule: Box<dyn SteeringRule> = match config.rule.as_str() {
+        // The default curves were tuned for steps of 1/60s.
+        "separation" => Box::new(Separation {
+            curve: config.curve_or(36.0),
+        }),
+        "cohesion" => Box::new(Cohesion {
+            curve: config.curve_or(0.36),
+        }),
+        "alignment" => Box::new(Alignment {
+            curve: config.curve_or(1e-2),
+        }),
+        "attraction" => Box::new(Attraction {
+            curve: config.curve_or(3.6),
+        }),
+        "avoidance" => Box::new(Avoidance {
+            curve: config.curve_or(1.0),
+        }),
+        "walls" => Box::new(Walls {
+            curve: config.curve_or(1.0),
+        }),
+        "flee" => Box::new(Flee {
+            curve: config.curve_or(2.0),
+        }),
+        "flash_expansion" => Box::new(FlashExpansion {
+            curve: config.curve_or(0.5),
+        }),
+        "guidance" => Box::new(Guidance {
+            curve: config.curve_or(60.0),
+        }),
+        "path_following" => Box::new(PathFollowing::new(config.curve_or(4.0))),
+        "attractors" => Box::new(Attractors {
+            curve: config.curve_or(1.0),
+        }),
+        "flow" => Box::new(FlowForce {
+            curve: config
+                .curve
+                .clone()
+                .unwrap_or_else(|| ResponseCurve::polynomial(1.0, 1.0)),
+        }),
+        name => {
+            let factory = CUSTOM_RULES.with(|rules| rules.borrow().get(name).cloned())?;
+            factory(config)
+        }
+    };
+    Some(rule)
+}
+
+/// The rules of `BoidsParams::rules`, created once and recreated when the
+/// configuration changes.
+pub(crate) struct Pipeline {
+    config: Vec<RuleConfig>,
+    stages: Vec<Stage>,
+}
+
+struct Stage {
+    weight: f32,
+    /// Overrides `BoidsParams::perception`.
+    perception: Option<Perception>,
+    rule: Box<dyn SteeringRule>,
+}
+
+impl Pipeline {
+    /// Unknown rules are left out: scenarios check their rules when loaded,
+    /// and `BoidsSimulation::unknown_rules` lists them otherwise.
+    pub fn new(config: &[RuleConfig]) -> Pipeline {
+        let stages = config
+            .iter()
+            .filter_map(|c| {
+                Some(Stage {
+                    weight: c.weight,
+                    perception: c.perception,
+                    rule: create_rule(c)?,
+                })
+            })
+            .collect();
+        Pipeline {
+            config: config.to_vec(),
+            stages,
+        }
+    }
+
+    pub fn sync(&mut self, config: &[RuleConfig]) {
+        if self.config != config {
+            *self = Pipeline::new(config);
+        }
+    }
+
+    /// Prepares every rule for a step.
+    pub fn prepare(&mut self, params: &BoidsParams) {
+        for stage in &mut self.stages {
+            stage.rule.prepare(params);
+        }
+    }
+
+    /// Perception range of the pipeline.
+    pub fn range(&self, params: &BoidsParams) -> f32 {
+        self.stages
+            .iter()
+            .map(|stage| stage.rule.range(params))
+            .fold(0.0, f32::max)
+    }
+
+    /// Weighted sum of the accelerations of every rule. Each rule only gets
+    /// the neighbors in its perception cone.
+    pub fn acceleration(&self, params: &BoidsParams, boid: &Agent) -> Vector3<f32> {
+        let mut acceleration = Vector3::zeros();
+        let mut visible = Vec::new();
+        for stage in &self.stages {
+            let perception = stage
+                .perception
+                .as_ref()
+                .unwrap_or_else(|| params.perception_of(boid.species));
+            let seen;
+            let boid = if perception.is_omnidirectional() {
+                boid
+            } else {
+                visible.clear();
+                visible.extend(
+                    boid.neighbors
+                        .iter()
+                        .filter(|n| perception.sees(&boid.velocity, &n.offset))
+                        .cloned(),
+                );
+                seen = Agent {
+                    neighbors: &visible,
+                    ..*boid
+                };
+                &seen
+            };
+
+            let range = stage.rule.range(params);
+            let mut sum = stage.rule.boid(params, boid);
+            for neighbor in boid.neighbors.iter().filter(|n| n.distance <= range) {
+                sum += stage.rule.pair(params, boid, neighbor);
+            }
+            acceleration += sum * stage.weight;
+        }
+        acceleration
+    }
+}
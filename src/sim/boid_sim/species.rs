@@ -0,0 +1,152 @@
+use super::{Boid, BoidsParams, Perception};
+use serde::{Deserialize, Serialize};
+
+/// A kind of boid, as written in `[[params.species]]` tables of scenario
+/// files. Boids refer to their species by its index in `BoidsParams::species`;
+/// boids of a species that isn't listed behave as in a single-species flock.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Species {
+    pub name: String,
+    /// Share of the spawned boids that belong to the species, relative to the
+    /// shares of the other species.
+    pub share: f32,
+    /// Color of the boids when rendered, as RGB in `[0, 1]`.
+    pub color: [f32; 3],
+    /// Size of the boids when rendered, relative to the scenario's `scale`.
+    pub scale: f32,
+    /// Speed limits of the species, instead of `min_speed` and `max_speed`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_speed: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_speed: Option<f32>,
+    /// Perception cone of the species, instead of `BoidsParams::perception`.
+    /// Rules with their own cone still use it.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub perception: Option<Perception>,
+    /// How boids of the species respond to neighbors of each species, by
+    /// index. Missing entries use `Interaction::default()`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub interactions: Vec<Interaction>,
+}
+
+impl Default for Species {
+    fn default() -> Species {
+        Species {
+            name: String::new(),
+            share: 1.0,
+            color: [1.0, 0.0, 0.0],
+            scale: 1.0,
+            min_speed: None,
+            max_speed: None,
+            perception: None,
+            interactions: Vec::new(),
+        }
+    }
+}
+
+/// How boids of a species respond to neighbors of another species (or of
+/// their own). Weights scale the `separation`, `cohesion` and `alignment`
+/// rules, and missing ranges are the ones of `BoidsParams`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Interaction {
+    pub separation: f32,
+    pub cohesion: f32,
+    pub alignment: f32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub separation_range: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cohesion_range: Option<f32>,
+}
+
+static DEFAULT_INTERACTION: Interaction = Interaction {
+    separation: 1.0,
+    cohesion: 1.0,
+    alignment: 1.0,
+    separation_range: None,
+    cohesion_range: None,
+};
+
+impl Default for Interaction {
+    fn default() -> Interaction {
+        DEFAULT_INTERACTION.clone()
+    }
+}
+
+impl Interaction {
+    pub fn separation_range(&self, params: &BoidsParams) -> f32 {
+        self.separation_range.unwrap_or(params.separation_range)
+    }
+
+    pub fn cohesion_range(&self, params: &BoidsParams) -> f32 {
+        self.cohesion_range.unwrap_or(params.cohesion_range)
+    }
+}
+
+impl BoidsParams {
+    /// How boids of species `a` respond to neighbors of species `b`.
+    pub fn interaction(&self, a: usize, b: usize) -> &Interaction {
+        self.species
+            .get(a)
+            .and_then(|species| species.interactions.get(b))
+            .unwrap_or(&DEFAULT_INTERACTION)
+    }
+
+    /// Speed limits of boids of species `species`.
+    pub fn speed_limits(&self, species: usize) -> (f32, f32) {
+        match self.species.get(species) {
+            Some(species) => (
+                species.min_speed.unwrap_or(self.min_speed),
+                species.max_speed.unwrap_or(self.max_speed),
+            ),
+            None => (self.min_speed, self.max_speed),
+        }
+    }
+
+    /// Perception cone of boids of species `species`.
+    pub fn perception_of(&self, species: usize) -> &Perception {
+        self.species
+            .get(species)
+            .and_then(|species| species.perception.as_ref())
+            .unwrap_or(&self.perception)
+    }
+
+    /// Largest of `range(interaction)` over every interaction, or `default`
+    /// if larger.
+    pub(crate) fn max_interaction_range<F>(&self, default: f32, range: F) -> f32
+    where
+        F: Fn(&Interaction) -> Option<f32>,
+    {
+        self.species
+            .iter()
+            .flat_map(|species| &species.interactions)
+            .filter_map(range)
+            .fold(default, f32::max)
+    }
+}
+
+/// Splits `boids` into consecutive runs, one per species, with lengths
+/// proportional to the species' shares.
+pub(crate) fn assign_species(boids: &mut [Boid], species: &[Species]) {
+    let total: f32 = species.iter().map(|species| species.share.max(0.0)).sum();
+    if species.is_empty() || total <= 0.0 {
+        return;
+    }
+
+    let n = boids.len();
+    let mut start = 0;
+    let mut cumulated = 0.0;
+    for (index, s) in species.iter().enumerate() {
+        cumulated += s.share.max(0.0);
+        let end = if index + 1 == species.len() {
+            n
+        } else {
+            ((cumulated / total * n as f32).round() as usize).min(n)
+        };
+        for boid in &mut boids[start..end.max(start)] {
+            boid.species = index;
+        }
+        start = end.max(start);
+    }
+}
@@ -0,0 +1,44 @@
+use std::fmt;
+
+/// Counters accumulated over the steps of a `BoidsSimulation`, to measure
+/// where the time goes. Times are in seconds.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StepStats {
+    pub steps: u64,
+    /// Number of times the neighbor index was rebuilt. Multi-stage
+    /// integrators look for neighbors several times per step.
+    pub index_rebuilds: u64,
+    /// Time spent rebuilding the neighbor index.
+    pub index_time: f64,
+    /// Time spent querying the neighbor index.
+    pub neighbor_time: f64,
+    /// Time spent evaluating steering rules.
+    pub rule_time: f64,
+    /// Time spent in `update`, including the above.
+    pub total_time: f64,
+}
+
+impl StepStats {
+    pub fn mean_step_time(&self) -> f64 {
+        if self.steps == 0 {
+            return 0.0;
+        }
+        self.total_time / self.steps as f64
+    }
+}
+
+impl fmt::Display for StepStats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let per_step = |time: f64| time * 1e3 / self.steps.max(1) as f64;
+        write!(
+            f,
+            "{} steps, {:.3}ms/step (index {:.3}ms, neighbors {:.3}ms, rules {:.3}ms), {} index rebuilds",
+            self.steps,
+            per_step(self.total_time),
+            per_step(self.index_time),
+            per_step(self.neighbor_time),
+            per_step(self.rule_time),
+            self.index_rebuilds,
+        )
+    }
+}
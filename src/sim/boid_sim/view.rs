@@ -0,0 +1,367 @@
+use super::{BoidsSimulation, Boundary, BoundaryMode, Flow, Obstacle, Species};
+use kiss3d::{resource::Mesh, scene::SceneNode, window::Window};
+use nalgebra::{Point3, Rotation3, Vector3};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+thread_local! {
+    pub static BOID_MESH: Rc<RefCell<Mesh>> = Rc::new(RefCell::new(Mesh::new(
+        vec![
+            Point3::<f32>::new(
+                (std::f32::consts::PI * 1.0 / 2.0).cos(),
+                0.0,
+                (std::f32::consts::PI * 1.0 / 2.0).sin(),
+            ),
+            Point3::<f32>::new(
+                (std::f32::consts::PI * 7.0 / 6.0).cos(),
+                0.0,
+                (std::f32::consts::PI * 7.0 / 6.0).sin(),
+            ),
+            Point3::<f32>::new(
+                (std::f32::consts::PI * 11.0 / 6.0).cos(),
+                0.0,
+                (std::f32::consts::PI * 11.0 / 6.0).sin(),
+            ),
+            Point3::<f32>::new(0.0, 3.0, 0.0),
+        ],
+        vec![
+            Point3::<u16>::new(2, 1, 0),
+            Point3::<u16>::new(0, 1, 3),
+            Point3::<u16>::new(1, 2, 3),
+            Point3::<u16>::new(3, 2, 0),
+        ],
+        None,
+        None,
+        false,
+    )));
+}
+
+/// Renders the state of a `BoidsSimulation` into a kiss3d scene.
+///
+/// The view owns one scene node per boid, and `sync` copies the simulation
+/// state over to these nodes. The simulation itself never touches the scene.
+/// Nodes are matched to boids by id, and follow boids being added to or
+/// removed from the flock. Obstacles are drawn once, when the view is created,
+/// and paths with lines at every frame by `draw`.
+/// Boids take the color and scale of their species, and informed boids the
+/// color of `leaders`. Predators are drawn larger and darker than boids, and
+/// attractors as blue spheres, or orange ones for repulsors.
+pub struct BoidsView {
+    group: SceneNode,
+    scale: f32,
+    nodes: HashMap<usize, SceneNode>,
+    /// Translation and velocity of each boid before the last step.
+    previous: HashMap<usize, (Vector3<f32>, Vector3<f32>)>,
+    predator_nodes: Vec<SceneNode>,
+    /// Translation and velocity of each predator before the last step.
+    previous_predators: Vec<(Vector3<f32>, Vector3<f32>)>,
+    attractor_nodes: Vec<SceneNode>,
+}
+
+impl BoidsView {
+    pub fn new(sim: &BoidsSimulation, scale: f32, scene: &mut SceneNode) -> BoidsView {
+        let mut view = BoidsView {
+            group: scene.add_group(),
+            scale,
+            nodes: HashMap::new(),
+            previous: HashMap::new(),
+            predator_nodes: Vec::new(),
+            previous_predators: Vec::new(),
+            attractor_nodes: Vec::new(),
+        };
+        for obstacle in &sim.params().obstacles {
+            add_obstacle(&mut view.group, obstacle);
+        }
+        let boundary = &sim.params().boundary;
+        if boundary.mode != BoundaryMode::Open {
+            let size = boundary.size();
+            let mut node = view.group.add_cube(size.x, size.y, size.z);
+            node.set_local_translation(((boundary.min + boundary.max) / 2.0).into());
+            node.set_surface_rendering_activation(false);
+            node.set_lines_width(1.0);
+            node.set_color(0.5, 0.5, 0.5);
+        }
+        view.record(sim);
+        view.sync(sim, 1.0);
+        view
+    }
+
+    /// Remembers the current state of `sim`. Call it before each step so that
+    /// `sync` can interpolate between the last two steps.
+    pub fn record(&mut self, sim: &BoidsSimulation) {
+        let flock = sim.flock();
+        self.previous.clear();
+        self.previous.extend(
+            flock
+                .ids()
+                .iter()
+                .zip(flock.translations().iter().zip(flock.velocities()))
+                .map(|(&id, (&translation, &velocity))| (id, (translation, velocity))),
+        );
+        self.previous_predators.clear();
+        self.previous_predators.extend(
+            sim.predators()
+                .iter()
+                .map(|predator| (predator.translation, predator.velocity)),
+        );
+    }
+
+    /// Moves the nodes `alpha` of the way between the recorded state and the
+    /// current state of `sim`.
+    pub fn sync(&mut self, sim: &BoidsSimulation, alpha: f32) {
+        let flock = sim.flock();
+
+        if self.nodes.len() != flock.len()
+            || flock.ids().iter().any(|id| !self.nodes.contains_key(id))
+        {
+            self.nodes.retain(|&id, node| {
+                let alive = flock.index_of(id).is_some();
+                if !alive {
+                    node.unlink();
+                }
+                alive
+            });
+            for (i, &id) in flock.ids().iter().enumerate() {
+                if !self.nodes.contains_key(&id) {
+                    let mut node = self.add_node(sim.params().species.get(flock.species()[i]));
+                    if flock.informed()[i] {
+                        let [r, g, b] = sim.params().leaders.color;
+                        node.set_color(r, g, b);
+                    }
+                    self.nodes.insert(id, node);
+                }
+            }
+        }
+
+        let boundary = &sim.params().boundary;
+        for (i, id) in flock.ids().iter().enumerate() {
+            let current = (flock.translations()[i], flock.velocities()[i]);
+            let previous = self.previous.get(id).cloned().unwrap_or(current);
+            place(
+                self.nodes.get_mut(id).unwrap(),
+                boundary,
+                previous,
+                current,
+                alpha,
+            );
+        }
+
+        let predators = sim.predators();
+        while self.predator_nodes.len() > predators.len() {
+            self.predator_nodes.pop().unwrap().unlink();
+        }
+        while self.predator_nodes.len() < predators.len() {
+            let node = add_mesh_node(&mut self.group, self.scale * PREDATOR_SCALE, PREDATOR_COLOR);
+            self.predator_nodes.push(node);
+        }
+        for (i, predator) in predators.iter().enumerate() {
+            let current = (predator.translation, predator.velocity);
+            let previous = self.previous_predators.get(i).cloned().unwrap_or(current);
+            place(
+                &mut self.predator_nodes[i],
+                boundary,
+                previous,
+                current,
+                alpha,
+            );
+        }
+
+        // Attractors may be added, removed or changed between two steps.
+        let attractors = &sim.params().attractors;
+        while self.attractor_nodes.len() > attractors.len() {
+            self.attractor_nodes.pop().unwrap().unlink();
+        }
+        while self.attractor_nodes.len() < attractors.len() {
+            let node = self.group.add_sphere(self.scale * ATTRACTOR_SCALE);
+            self.attractor_nodes.push(node);
+        }
+        let time = sim.time() - f64::from(sim.params().dt) * f64::from(1.0 - alpha);
+        for (node, attractor) in self.attractor_nodes.iter_mut().zip(attractors) {
+            let (r, g, b) = if attractor.strength >= 0.0 {
+                ATTRACTOR_COLOR
+            } else {
+                REPULSOR_COLOR
+            };
+            node.set_color(r, g, b);
+            node.set_local_translation(attractor.position_at(time.max(0.0) as f32).into());
+        }
+    }
+
+    /// Draws the paths of `sim`.
+    pub fn draw(&self, sim: &BoidsSimulation, window: &mut Window) {
+        for path in &sim.params().paths {
+            let [r, g, b] = path.color;
+            let color = Point3::new(r, g, b);
+            for segment in path.points().windows(2) {
+                window.draw_line(&segment[0].into(), &segment[1].into(), &color);
+            }
+        }
+
+        let time = sim.time() as f32;
+        for flow in sim.params().flows.iter().filter(|flow| flow.arrows) {
+            draw_flow(sim, flow, time, window);
+        }
+    }
+
+    /// Adds the node of a boid of species `species`, or of the default
+    /// species.
+    fn add_node(&mut self, species: Option<&Species>) -> SceneNode {
+        match species {
+            Some(species) => {
+                let [r, g, b] = species.color;
+                add_mesh_node(&mut self.group, self.scale * species.scale, (r, g, b))
+            }
+            None => add_mesh_node(&mut self.group, self.scale, BOID_COLOR),
+        }
+    }
+}
+
+const BOID_COLOR: (f32, f32, f32) = (1.0, 0.0, 0.0);
+const PREDATOR_COLOR: (f32, f32, f32) = (0.2, 0.2, 0.2);
+/// Size of predators relative to boids.
+const PREDATOR_SCALE: f32 = 2.5;
+const ATTRACTOR_COLOR: (f32, f32, f32) = (0.2, 0.5, 1.0);
+const REPULSOR_COLOR: (f32, f32, f32) = (1.0, 0.5, 0.0);
+/// Radius of attractors relative to the size of boids.
+const ATTRACTOR_SCALE: f32 = 0.3;
+
+/// Number of flow arrows along each axis of the lattice they are drawn on.
+const FLOW_ARROWS: usize = 6;
+const FLOW_COLOR: (f32, f32, f32) = (0.5, 0.7, 0.9);
+
+/// Draws `flow` as arrows on a lattice spanning the world box, or the flock
+/// when there is no box. Arrows are scaled so that the longest one is a bit
+/// shorter than the spacing of the lattice.
+fn draw_flow(sim: &BoidsSimulation, flow: &Flow, time: f32, window: &mut Window) {
+    let boundary = &sim.params().boundary;
+    let (min, max) = if boundary.mode != BoundaryMode::Open {
+        (boundary.min, boundary.max)
+    } else {
+        let translations = sim.flock().translations();
+        if translations.is_empty() {
+            return;
+        }
+        let (min, max) = translations.iter().fold(
+            (translations[0], translations[0]),
+            |(min, max), translation| {
+                (
+                    min.zip_map(translation, f32::min),
+                    max.zip_map(translation, f32::max),
+                )
+            },
+        );
+        let padding = Vector3::repeat(0.1);
+        (min - padding, max + padding)
+    };
+
+    let step = (max - min) / FLOW_ARROWS as f32;
+    let mut arrows = Vec::with_capacity(FLOW_ARROWS.pow(3));
+    for i in 0..FLOW_ARROWS.pow(3) {
+        let cell = Vector3::new(
+            i % FLOW_ARROWS,
+            i / FLOW_ARROWS % FLOW_ARROWS,
+            i / FLOW_ARROWS / FLOW_ARROWS,
+        )
+        .map(|k| k as f32 + 0.5);
+        let point = min + step.component_mul(&cell);
+        arrows.push((point, flow.sample(&point, time)));
+    }
+    let longest = arrows
+        .iter()
+        .map(|(_, value)| value.norm())
+        .fold(0.0, f32::max);
+    if longest == 0.0 {
+        return;
+    }
+
+    let (r, g, b) = FLOW_COLOR;
+    let color = Point3::new(r, g, b);
+    let length = step.min() * 0.8 / longest;
+    for (point, value) in arrows {
+        let tip = point + value * length;
+        window.draw_line(&point.into(), &tip.into(), &color);
+        // Two barbs, in a plane containing the arrow.
+        let back = (point - tip) * 0.3;
+        let side = back
+            .cross(&Vector3::z())
+            .try_normalize(1e-6)
+            .unwrap_or_else(Vector3::x)
+            * back.norm()
+            * 0.5;
+        window.draw_line(&tip.into(), &(tip + back + side).into(), &color);
+        window.draw_line(&tip.into(), &(tip + back - side).into(), &color);
+    }
+}
+
+fn add_mesh_node(group: &mut SceneNode, scale: f32, (r, g, b): (f32, f32, f32)) -> SceneNode {
+    let mut node = BOID_MESH.with(|m| {
+        group.add_mesh(
+            Rc::clone(m),
+            Vector3::<f32>::new(1.0, 1.0, 1.0) * 0.1 * scale,
+        )
+    });
+    node.set_color(r, g, b);
+    node
+}
+
+/// Moves `node` `alpha` of the way from the `previous` to the `current`
+/// translation and velocity.
+fn place(
+    node: &mut SceneNode,
+    boundary: &Boundary,
+    previous: (Vector3<f32>, Vector3<f32>),
+    current: (Vector3<f32>, Vector3<f32>),
+    alpha: f32,
+) {
+    let ((mut previous_translation, previous_velocity), (translation, velocity)) =
+        (previous, current);
+    if boundary.is_periodic() {
+        // An agent that wrapped around the box moves from the image of its
+        // previous translation nearest to the current one.
+        previous_translation = translation - boundary.offset(&previous_translation, &translation);
+    }
+
+    let velocity = previous_velocity.lerp(&velocity, alpha);
+    if let Some(rotation) = Rotation3::<f32>::rotation_between(&Vector3::<f32>::y(), &velocity) {
+        node.set_local_rotation(rotation.into());
+    }
+    node.set_local_translation(previous_translation.lerp(&translation, alpha).into());
+}
+
+/// Side of the square drawn for an infinite plane.
+const PLANE_SIZE: f32 = 10.0;
+
+fn add_obstacle(group: &mut SceneNode, obstacle: &Obstacle) {
+    let mut node = match obstacle {
+        Obstacle::Sphere { center, radius } => {
+            let mut node = group.add_sphere(*radius);
+            node.set_local_translation((*center).into());
+            node
+        }
+        Obstacle::Aabb { min, max } => {
+            let size = max - min;
+            let mut node = group.add_cube(size.x, size.y, size.z);
+            node.set_local_translation(((min + max) / 2.0).into());
+            node
+        }
+        Obstacle::Plane { point, normal } => {
+            let mut node = group.add_quad(PLANE_SIZE, PLANE_SIZE, 1, 1);
+            node.enable_backface_culling(false);
+            if let Some(rotation) = Rotation3::rotation_between(&Vector3::z(), normal) {
+                node.set_local_rotation(rotation.into());
+            }
+            node.set_local_translation((*point).into());
+            node
+        }
+        Obstacle::Capsule { a, b, radius } => {
+            let mut node = group.add_capsule(*radius, (b - a).norm());
+            if let Some(rotation) = Rotation3::rotation_between(&Vector3::y(), &(b - a)) {
+                node.set_local_rotation(rotation.into());
+            }
+            node.set_local_translation(((a + b) / 2.0).into());
+            node
+        }
+    };
+    node.set_color(0.5, 0.5, 0.5);
+}
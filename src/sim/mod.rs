@@ -1,5 +1,17 @@
 mod boid_sim;
+mod scenario;
 pub mod sims;
 mod simulation;
 
+pub use boid_sim::{
+    register_rule, Agent, Alignment, Attraction, Attractor, Attractors, Avoidance, Boid,
+    BoidsParams, BoidsSimulation, BoidsSimulationBuilder, BoidsView, Boundary, BoundaryMode,
+    BruteForceIndex, Capture, Cohesion, FlashExpansion, Flee, Flock, Flow, FlowEffect, FlowField,
+    FlowForce, FlowGrid, Followers, GridIndex, Guidance, Integrator, Interaction, Keyframe,
+    LeaderParams, Leadership, Motion, Neighbor, NeighborIndex, NeighborIndexKind, NoiseParams,
+    Obstacle, Path, PathFollowing, Perception, Predator, PredatorParams, PredatorTarget,
+    RTreeIndex, ResponseCurve, RuleConfig, Separation, Species, SteeringRule, StepStats, Walls,
+    DEFAULT_TIMESTEP,
+};
+pub use scenario::*;
 pub use simulation::*;
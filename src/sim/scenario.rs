@@ -0,0 +1,322 @@
+use super::boid_sim::{create_rule, BoidsParams, BoidsSimulation, BoidsView, FlowField};
+use super::Simulation;
+use nalgebra::Vector3;
+use serde::{Deserialize, Serialize};
+use std::{fmt, fs, io, path::Path};
+
+/// Scenarios shipped with the crate, by name.
+pub const BUNDLED_SCENARIOS: &[(&str, &str)] = &[
+    (
+        "no_constraints",
+        include_str!("../../scenarios/no_constraints.toml"),
+    ),
+    ("cohesion", include_str!("../../scenarios/cohesion.toml")),
+    (
+        "separation",
+        include_str!("../../scenarios/separation.toml"),
+    ),
+    ("alignment", include_str!("../../scenarios/alignment.toml")),
+    (
+        "attraction",
+        include_str!("../../scenarios/attraction.toml"),
+    ),
+    ("coherence", include_str!("../../scenarios/coherence.toml")),
+    (
+        "neighbors5_small",
+        include_str!("../../scenarios/neighbors5_small.toml"),
+    ),
+    (
+        "neighbors5_big",
+        include_str!("../../scenarios/neighbors5_big.toml"),
+    ),
+    ("leaders", include_str!("../../scenarios/leaders.toml")),
+    ("obstacles", include_str!("../../scenarios/obstacles.toml")),
+    ("periodic", include_str!("../../scenarios/periodic.toml")),
+    ("predators", include_str!("../../scenarios/predators.toml")),
+    ("path", include_str!("../../scenarios/path.toml")),
+    (
+        "attractors",
+        include_str!("../../scenarios/attractors.toml"),
+    ),
+    (
+        "two_species",
+        include_str!("../../scenarios/two_species.toml"),
+    ),
+    ("vicsek", include_str!("../../scenarios/vicsek.toml")),
+    ("wind", include_str!("../../scenarios/wind.toml")),
+];
+
+/// Region boids are spawned in.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SpawnRegion {
+    pub center: Vector3<f32>,
+    pub radius: f32,
+}
+
+impl Default for SpawnRegion {
+    fn default() -> SpawnRegion {
+        SpawnRegion {
+            center: Vector3::<f32>::new(0.0, 0.0, 0.0),
+            radius: 1e-1,
+        }
+    }
+}
+
+/// Description of a `BoidsSimulation`, as loaded from a TOML scenario file.
+///
+/// ```toml
+/// description = "A small flock."
+/// boids = 100
+/// scale = 0.03
+/// seed = 42
+///
+/// [spawn]
+/// radius = 0.1
+///
+/// [params]
+/// separation_range = 0.03
+/// cohesion_range = 0.15
+/// ```
+///
+/// Every field is optional and defaults to `Scenario::default()`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Scenario {
+    pub description: String,
+    /// Number of boids.
+    pub boids: usize,
+    /// Size of the boids when rendered.
+    pub scale: f32,
+    /// Seed of the simulation RNG. A random seed is picked when missing.
+    pub seed: Option<u64>,
+    pub spawn: SpawnRegion,
+    pub params: BoidsParams,
+}
+
+impl Default for Scenario {
+    fn default() -> Scenario {
+        Scenario {
+            description: String::new(),
+            boids: 100,
+            scale: 0.03,
+            seed: None,
+            spawn: Default::default(),
+            params: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum ScenarioError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// No bundled scenario or demo has this name.
+    Unknown(String),
+    /// A parameter override does not match the scenario format.
+    InvalidOverride(String),
+    /// No built-in or registered steering rule has this name.
+    UnknownRule(String),
+    /// The file of a flow grid could not be loaded.
+    InvalidFlowGrid(String),
+    /// `params.dt` is not a positive, finite duration.
+    InvalidTimestep(f32),
+}
+
+impl fmt::Display for ScenarioError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioError::Io(err) => write!(f, "could not read scenario: {}", err),
+            ScenarioError::Parse(err) => write!(f, "invalid scenario: {}", err),
+            ScenarioError::Unknown(name) => write!(f, "unknown scenario: {}", name),
+            ScenarioError::InvalidOverride(msg) => write!(f, "invalid override: {}", msg),
+            ScenarioError::UnknownRule(name) => write!(f, "unknown steering rule: {}", name),
+            ScenarioError::InvalidFlowGrid(msg) => write!(f, "invalid flow grid: {}", msg),
+            ScenarioError::InvalidTimestep(dt) => {
+                write!(f, "invalid timestep: {} (must be positive and finite)", dt)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ScenarioError {}
+
+impl From<io::Error> for ScenarioError {
+    fn from(err: io::Error) -> ScenarioError {
+        ScenarioError::Io(err)
+    }
+}
+
+impl From<toml::de::Error> for ScenarioError {
+    fn from(err: toml::de::Error) -> ScenarioError {
+        ScenarioError::Parse(err)
+    }
+}
+
+impl Scenario {
+    /// Parses a scenario. Files of flow grids are relative to the working
+    /// directory.
+    pub fn from_toml(source: &str) -> Result<Scenario, ScenarioError> {
+        Scenario::parse(source, Path::new(""))
+    }
+
+    /// Reads a scenario file. Files of flow grids are relative to the
+    /// directory of the scenario.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Scenario, ScenarioError> {
+        let path = path.as_ref();
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+        Scenario::parse(&fs::read_to_string(path)?, dir)
+    }
+
+    fn parse(source: &str, dir: &Path) -> Result<Scenario, ScenarioError> {
+        let mut scenario: Scenario = toml::from_str(source)?;
+        scenario.check_timestep()?;
+        scenario.check_rules()?;
+        scenario.load_flow_grids(dir)?;
+        Ok(scenario)
+    }
+
+    /// Looks up one of the `BUNDLED_SCENARIOS`.
+    pub fn bundled(name: &str) -> Option<Scenario> {
+        BUNDLED_SCENARIOS
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, source)| Scenario::from_toml(source).expect("invalid bundled scenario"))
+    }
+
+    pub fn to_toml(&self) -> String {
+        toml::to_string(self).expect("scenario is always serializable")
+    }
+
+    /// Overrides a single field, given as a dotted path into the scenario file
+    /// and a TOML value, e.g. `set("params.cohesion_range", "0.2")`. Values
+    /// that don't parse as TOML are taken as strings.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ScenarioError> {
+        let value = match format!("value = {}", value).parse::<toml::Value>() {
+            Ok(toml::Value::Table(mut table)) => table.remove("value").unwrap(),
+            _ => toml::Value::String(value.to_string()),
+        };
+
+        let mut root = toml::Value::try_from(&*self)
+            .map_err(|err| ScenarioError::InvalidOverride(err.to_string()))?;
+        let mut table = &mut root;
+        let mut path = key.split('.').peekable();
+        while let Some(segment) = path.next() {
+            let entries = table
+                .as_table_mut()
+                .ok_or_else(|| ScenarioError::InvalidOverride(format!("{} is not a table", key)))?;
+            if path.peek().is_none() {
+                entries.insert(segment.to_string(), value);
+                break;
+            }
+            table = entries
+                .entry(segment.to_string())
+                .or_insert_with(|| toml::Value::Table(Default::default()));
+        }
+
+        let mut scenario: Scenario = root
+            .try_into()
+            .map_err(|err| ScenarioError::InvalidOverride(format!("{}: {}", key, err)))?;
+        scenario.check_timestep()?;
+        scenario.check_rules()?;
+        scenario.load_flow_grids(Path::new(""))?;
+        *self = scenario;
+        Ok(())
+    }
+
+    fn check_timestep(&self) -> Result<(), ScenarioError> {
+        let dt = self.params.dt;
+        if dt > 0.0 && dt.is_finite() {
+            Ok(())
+        } else {
+            Err(ScenarioError::InvalidTimestep(dt))
+        }
+    }
+
+    fn check_rules(&self) -> Result<(), ScenarioError> {
+        for rule in &self.params.rules {
+            if create_rule(rule).is_none() {
+                return Err(ScenarioError::UnknownRule(rule.rule.clone()));
+            }
+        }
+        Ok(())
+    }
+
+    fn load_flow_grids(&mut self, dir: &Path) -> Result<(), ScenarioError> {
+        for flow in &mut self.params.flows {
+            if let FlowField::Grid { file, .. } = &flow.field {
+                let file = file.clone();
+                flow.field
+                    .load(dir)
+                    .map_err(|err| ScenarioError::InvalidFlowGrid(format!("{}: {}", file, err)))?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Builds the simulation described by the scenario, seeded with `seed`.
+    pub fn build(&self, seed: u64) -> BoidsSimulation {
+        BoidsSimulation::builder()
+            .seed(seed)
+            .params(self.params.clone())
+            .sphere_at(self.spawn.center, self.boids, self.spawn.radius)
+            .build()
+    }
+}
+
+/// Runs and renders a `Scenario`.
+pub struct ScenarioSim {
+    sim: BoidsSimulation,
+    view: BoidsView,
+    scenario: Scenario,
+    seed: u64,
+}
+
+impl ScenarioSim {
+    pub fn new(scenario: &Scenario, seed: u64, scene: &mut kiss3d::scene::SceneNode) -> Self {
+        let sim = scenario.build(seed);
+        let view = BoidsView::new(&sim, scenario.scale, scene);
+
+        ScenarioSim {
+            sim,
+            view,
+            scenario: scenario.clone(),
+            seed,
+        }
+    }
+
+    pub fn sim(&self) -> &BoidsSimulation {
+        &self.sim
+    }
+}
+
+impl Simulation for ScenarioSim {
+    fn update(&mut self) {
+        self.view.record(&self.sim);
+        self.sim.update();
+    }
+
+    fn timestep(&self) -> f32 {
+        self.sim.params().dt
+    }
+
+    fn render(&mut self, alpha: f32) {
+        self.view.sync(&self.sim, alpha);
+    }
+
+    fn draw(&self, window: &mut kiss3d::window::Window) {
+        self.view.draw(&self.sim, window);
+    }
+
+    fn params_mut(&mut self) -> Option<&mut BoidsParams> {
+        Some(self.sim.params_mut())
+    }
+
+    fn scenario(&self) -> Option<Scenario> {
+        Some(Scenario {
+            seed: Some(self.seed),
+            params: self.sim.params().clone(),
+            ..self.scenario.clone()
+        })
+    }
+}
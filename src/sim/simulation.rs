@@ -1,4 +1,63 @@
+use super::{BoidsParams, Scenario, DEFAULT_TIMESTEP};
+use kiss3d::window::Window;
+
+/// Anything that can be run and rendered by `AppState`.
+///
+/// Implement this trait to plug your own simulation into the app, then start
+/// it with `run_simulation`.
 pub trait Simulation {
-    fn init(scene: &mut kiss3d::scene::SceneNode) -> Self;
+    /// Advances the simulation by one step of `timestep` seconds.
     fn update(&mut self) {}
+
+    /// Duration of a step, in seconds. The app steps the simulation at this
+    /// fixed rate, independently of the frame rate.
+    fn timestep(&self) -> f32 {
+        DEFAULT_TIMESTEP
+    }
+
+    /// Updates the scene to show the simulation `alpha` of the way, in
+    /// `[0, 1]`, between the previous step and the current one.
+    fn render(&mut self, _alpha: f32) {}
+
+    /// Draws what doesn't fit in scene nodes, such as lines, into `window`.
+    /// Called once per frame, after `render`.
+    fn draw(&self, _window: &mut Window) {}
+
+    /// Parameters the GUI can tune while the simulation runs, if any. Changes
+    /// apply from the next step on.
+    fn params_mut(&mut self) -> Option<&mut BoidsParams> {
+        None
+    }
+
+    /// A scenario reproducing the simulation with its current parameters, if
+    /// it can be described by one.
+    fn scenario(&self) -> Option<Scenario> {
+        None
+    }
+}
+
+impl<S: Simulation + ?Sized> Simulation for Box<S> {
+    fn update(&mut self) {
+        (**self).update();
+    }
+
+    fn timestep(&self) -> f32 {
+        (**self).timestep()
+    }
+
+    fn render(&mut self, alpha: f32) {
+        (**self).render(alpha);
+    }
+
+    fn draw(&self, window: &mut Window) {
+        (**self).draw(window);
+    }
+
+    fn params_mut(&mut self) -> Option<&mut BoidsParams> {
+        (**self).params_mut()
+    }
+
+    fn scenario(&self) -> Option<Scenario> {
+        (**self).scenario()
+    }
 }